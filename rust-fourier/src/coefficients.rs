@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::Path;
+
+use num_complex::Complex;
+use serde::Deserialize;
+
+// Sets shipped with the program, always cycled before the ones given as argument
+const BUILTINS: [&str; 10] = [
+    include_str!("../coefficients/01-three-harmonics.toml"),
+    include_str!("../coefficients/02-halving-harmonics.toml"),
+    include_str!("../coefficients/03-equal-harmonics.toml"),
+    include_str!("../coefficients/04-looped-circle.toml"),
+    include_str!("../coefficients/05-line.toml"),
+    include_str!("../coefficients/06-deltoid.toml"),
+    include_str!("../coefficients/07-flower.toml"),
+    include_str!("../coefficients/08-backward-harmonics.toml"),
+    include_str!("../coefficients/09-fractional-harmonics.toml"),
+    include_str!("../coefficients/10-rotated-deltoid.toml"),
+];
+
+#[derive(Deserialize)]
+struct TermEntry {
+    amplitude: f32,
+    frequency: f32,
+    // Radians
+    #[serde(default)]
+    phase: f32,
+}
+
+#[derive(Deserialize)]
+struct CoefficientFile {
+    term: Vec<TermEntry>,
+}
+
+// One circle of the chain, the coefficient holds its radius and starting angle
+#[derive(Clone, Copy)]
+pub struct Term {
+    pub coefficient: Complex<f32>,
+    pub frequency: f32,
+}
+
+impl Term {
+    // Offset of the end of the circle from its center at angle t
+    pub fn at(&self, t: f32) -> Complex<f32> {
+        self.coefficient * Complex::from_polar(1., self.frequency * t)
+    }
+}
+
+// Terms of toml or json, "[[term]] amplitude = 100, frequency = 1, phase = 0"
+fn parse(content: &str, json: bool) -> Result<Vec<Term>, String> {
+    let file: CoefficientFile = if json {
+        serde_json::from_str(content).map_err(|err| err.to_string())?
+    } else {
+        toml::from_str(content).map_err(|err| err.to_string())?
+    };
+
+    Ok(file
+        .term
+        .into_iter()
+        .map(|term| Term {
+            coefficient: Complex::from_polar(term.amplitude, term.phase),
+            frequency: term.frequency,
+        })
+        .collect())
+}
+
+pub fn load(path: &Path) -> Result<Vec<Term>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Couldn't read {}: {}", path.display(), err))?;
+    let json = path
+        .extension()
+        .is_some_and(|extension| extension == "json");
+    parse(&content, json).map_err(|err| format!("Couldn't load {}: {}", path.display(), err))
+}
+
+pub fn builtins() -> Vec<Vec<Term>> {
+    // The shipped files are known to parse
+    BUILTINS
+        .iter()
+        .map(|content| parse(content, false).unwrap())
+        .collect()
+}
+
+// Built-in sets followed by the ones of a single file or of every toml and json file of a
+// directory, by file name, the ones that fail to load are reported and left out
+pub fn load_all(path: Option<&Path>) -> Vec<Vec<Term>> {
+    let mut sets = builtins();
+    let Some(path) = path else {
+        return sets;
+    };
+
+    let mut files = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| {
+                matches!(
+                    file.extension().and_then(|extension| extension.to_str()),
+                    Some("toml" | "json")
+                )
+            })
+            .collect(),
+        Err(_) => vec![path.to_path_buf()],
+    };
+    files.sort();
+    if files.is_empty() {
+        eprintln!("No coefficients in {}", path.display());
+    }
+
+    sets.extend(files.iter().filter_map(|file| match load(file) {
+        Ok(terms) => Some(terms),
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        }
+    }));
+    sets
+}
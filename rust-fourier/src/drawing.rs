@@ -0,0 +1,149 @@
+use std::f32::consts::TAU;
+
+use num_complex::Complex;
+
+use crate::coefficients::Term;
+
+// Points evenly spaced along the closed path through the points, the last one joining the first
+pub fn resample(points: &[(f32, f32)], count: usize) -> Vec<(f32, f32)> {
+    let segment = |i: usize| (points[i], points[(i + 1) % points.len()]);
+    let length = |((x1, y1), (x2, y2)): ((f32, f32), (f32, f32))| (x2 - x1).hypot(y2 - y1);
+    let total: f32 = (0..points.len()).map(|i| length(segment(i))).sum();
+
+    let mut resampled = Vec::with_capacity(count);
+    let (mut i, mut start) = (0, 0.);
+    for j in 0..count {
+        let distance = total * j as f32 / count as f32;
+        while i < points.len() - 1 && start + length(segment(i)) < distance {
+            start += length(segment(i));
+            i += 1;
+        }
+
+        let ((x1, y1), (x2, y2)) = segment(i);
+        let k = match length(segment(i)) {
+            l if l > 0. => ((distance - start) / l).min(1.),
+            _ => 0.,
+        };
+        resampled.push((x1 + (x2 - x1) * k, y1 + (y2 - y1) * k));
+    }
+    resampled
+}
+
+pub fn centroid(points: &[(f32, f32)]) -> (f32, f32) {
+    let (x, y) = points
+        .iter()
+        .fold((0., 0.), |(x, y), point| (x + point.0, y + point.1));
+    (x / points.len() as f32, y / points.len() as f32)
+}
+
+// Pen positions are compared to this many of the points at most
+const ERROR_SAMPLES: usize = 512;
+
+// In place radix-2 fast Fourier transform, the length being a power of two
+fn fft(values: &mut [Complex<f32>]) {
+    let n = values.len();
+    if n < 2 {
+        return;
+    }
+
+    // Bit reversed order, so that the butterflies work on neighbouring halves
+    let bits = usize::BITS - n.trailing_zeros();
+    (0..n).for_each(|i| {
+        let j = i.reverse_bits() >> bits;
+        if i < j {
+            values.swap(i, j);
+        }
+    });
+
+    let mut size = 2;
+    while size <= n {
+        values.chunks_mut(size).for_each(|chunk| {
+            let (even, odd) = chunk.split_at_mut(size / 2);
+            even.iter_mut()
+                .zip(odd.iter_mut())
+                .enumerate()
+                .for_each(|(k, (a, b))| {
+                    let twiddle = Complex::from_polar(1., -TAU * k as f32 / size as f32);
+                    let product = *b * twiddle;
+                    *b = *a - product;
+                    *a += product;
+                });
+        });
+        size *= 2;
+    }
+}
+
+// Naive discrete Fourier transform, for lengths the fft can't handle
+fn dft(values: &[Complex<f32>]) -> Vec<Complex<f32>> {
+    let n = values.len() as f32;
+    (0..values.len())
+        .map(|k| {
+            values
+                .iter()
+                .enumerate()
+                .map(|(j, value)| value * Complex::from_polar(1., -TAU * k as f32 * j as f32 / n))
+                .sum()
+        })
+        .collect()
+}
+
+// Fourier transform of the points as epicycles turning around the centroid, the largest
+// ones first, with the fft when the number of points is a power of two
+pub fn epicycles(points: &[(f32, f32)]) -> Vec<Term> {
+    let n = points.len();
+    let mut values: Vec<Complex<f32>> = points.iter().map(|&(x, y)| Complex::new(x, y)).collect();
+    if n.is_power_of_two() {
+        fft(&mut values);
+    } else {
+        values = dft(&values);
+    }
+
+    // Frequencies past the middle are the negative ones
+    let mut epicycles: Vec<Term> = values
+        .into_iter()
+        .enumerate()
+        .skip(1)
+        .map(|(k, value)| Term {
+            coefficient: value / n as f32,
+            frequency: if k > n / 2 {
+                k as f32 - n as f32
+            } else {
+                k as f32
+            },
+        })
+        .collect();
+    epicycles.sort_by(|a, b| b.coefficient.norm().total_cmp(&a.coefficient.norm()));
+    epicycles
+}
+
+// End of the chain of epicycles turning around origin
+pub fn pen(terms: &[Term], origin: (f32, f32), t: f32) -> (f32, f32) {
+    let pen = terms
+        .iter()
+        .fold(Complex::new(origin.0, origin.1), |pen, term| {
+            pen + term.at(t)
+        });
+    (pen.re, pen.im)
+}
+
+// Pen positions at count even times over a turn
+pub fn trace(terms: &[Term], origin: (f32, f32), count: usize) -> Vec<(f32, f32)> {
+    (0..count)
+        .map(|j| pen(terms, origin, TAU * j as f32 / count as f32))
+        .collect()
+}
+
+// Root mean square distance between the points, taken at even times over a turn, and the
+// pen of the epicycles
+pub fn rms_error(terms: &[Term], origin: (f32, f32), points: &[(f32, f32)]) -> f32 {
+    let n = points.len();
+    let samples: Vec<usize> = (0..n).step_by((n / ERROR_SAMPLES).max(1)).collect();
+    let sum: f32 = samples
+        .iter()
+        .map(|&j| {
+            let (x, y) = pen(terms, origin, TAU * j as f32 / n as f32);
+            (x - points[j].0).powi(2) + (y - points[j].1).powi(2)
+        })
+        .sum();
+    (sum / samples.len() as f32).sqrt()
+}
@@ -1,72 +1,392 @@
+use macroquad::color::hsl_to_rgb;
 use macroquad::prelude::*;
+use num_complex::Complex;
 use std::f32::consts::PI;
+use std::path::PathBuf;
+
+mod coefficients;
+mod drawing;
+mod svg;
+
+use coefficients::Term;
 
 const TAU: f32 = PI * 2.;
 const STEPS: usize = 240;
+const ORIGIN: (f32, f32) = (400., 300.);
+// Points of a path given to the Fourier transform, the power of two above its own number
+// of points within these bounds
+const MIN_POINTS: usize = 256;
+const MAX_POINTS: usize = 4096;
+// Circles and arms smaller than this many pixels on screen are left out of the chain
+const MIN_RADIUS: f32 = 0.5;
+// Earlier approximations shown behind the current one in teaching mode
+const APPROXIMATIONS: usize = 8;
+// Follow camera: magnification at start and at most, change by wheel notch, and rate at
+// which it catches up with the pen, per second
+const FOLLOW_ZOOM: f32 = 8.;
+const MAX_ZOOM: f32 = 1000.;
+const ZOOM_FACTOR: f32 = 1.25;
+const FOLLOW_RATE: f32 = 5.;
+// Size of the picture in picture of the whole view relative to the window, and its margin
+const INSET_SCALE: f32 = 0.25;
+const INSET_MARGIN: f32 = 10.;
 
 static WHITE: Color = color_u8!(255, 255, 255, 255);
 
+// Epicycles at time t, the path they traced and the earlier approximations, with lines
+// as wide on screen whatever the world size of a pixel
+fn draw_scene(
+    terms: &[Term],
+    origin: (f32, f32),
+    t: f32,
+    points: &[(f32, f32)],
+    approximations: &[Vec<(f32, f32)>],
+    pixel: f32,
+) {
+    let mut pos = origin;
+
+    terms.iter().for_each(|term| {
+        let next_pos = Complex::new(pos.0, pos.1) + term.at(t);
+        let next_pos = (next_pos.re, next_pos.im);
+        let radius = term.coefficient.norm();
+        if radius >= MIN_RADIUS * pixel {
+            draw_circle_lines(pos.0, pos.1, radius, pixel, WHITE);
+            draw_line(pos.0, pos.1, next_pos.0, next_pos.1, pixel, WHITE);
+        }
+        pos = next_pos;
+    });
+
+    // Earlier approximations, fading and changing hue as they get older
+    approximations
+        .iter()
+        .rev()
+        .enumerate()
+        .for_each(|(age, approximation)| {
+            let mut color = hsl_to_rgb((0.6 + age as f32 / APPROXIMATIONS as f32) % 1., 0.8, 0.6);
+            color.a = 0.7 * (1. - age as f32 / APPROXIMATIONS as f32);
+            approximation
+                .iter()
+                .zip(approximation.iter().skip(1))
+                .for_each(|(point, next_point)| {
+                    draw_line(point.0, point.1, next_point.0, next_point.1, pixel, color)
+                });
+        });
+
+    points
+        .iter()
+        .zip(points.iter().skip(1))
+        .for_each(|(point, next_point)| {
+            draw_line(
+                point.0,
+                point.1,
+                next_point.0,
+                next_point.1,
+                2. * pixel,
+                WHITE,
+            )
+        });
+}
+
+// Number of the largest terms kept, None once it covers all of them
+fn keep(count: usize, total: usize) -> Option<usize> {
+    if count >= total {
+        None
+    } else {
+        Some(count)
+    }
+}
+
 #[macroquad::main("Fourier")]
 async fn main() {
     let step: f32 = TAU / STEPS as f32;
 
-    let mut trigos_fns = vec![
-        vec![(100., 1.), (50., 2.), (25., 3.)],
-        (0..10)
-            .map(|i| (150. / 2u32.pow(i) as f32, i as f32 + 1.))
-            .collect(),
-        vec![(50., 1.), (50., 2.), (50., 3.), (50., 4.)],
-        vec![(150., 1.), (75., 5.), (37.5, -1.)],
-        vec![(100., 1.), (100., -1.)],
-        vec![(150., 1.), (150., -2.)],
-        vec![(100., 1.), (100., -3.), (50., 5.)],
-        (0..20).map(|i| (10., -1. * i as f32)).collect(),
-        (0..20)
-            .map(|i| (i as f32 * 1.7, i as f32 / 10. * 2.))
-            .collect(),
-    ]
-    .into_iter()
-    .cycle();
-
-    let mut trigos: Vec<(f32, f32)> = trigos_fns.next().unwrap();
+    // Coefficient file, directory of coefficient files or svg file given as argument, the
+    // built-in sets play in any case
+    let path = std::env::args().nth(1).map(PathBuf::from);
+    let svg_path = path
+        .as_ref()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "svg"));
+    let sets = coefficients::load_all(path.as_deref().filter(|_| svg_path.is_none()));
+    let mut trigos_fns = sets.into_iter().cycle();
+    // Terms of the next set, the largest first, and the path drawn by all of them
+    let mut next_trigos = || -> (Vec<Term>, Vec<(f32, f32)>) {
+        let mut terms = trigos_fns.next().unwrap_or_default();
+        terms.sort_by(|a, b| b.coefficient.norm().total_cmp(&a.coefficient.norm()));
+        let source = drawing::trace(&terms, ORIGIN, STEPS);
+        (terms, source)
+    };
+
+    let (mut trigos, mut source) = next_trigos();
+    let mut origin = ORIGIN;
+
+    // Number of the largest terms kept, all of them when None, and the error it makes
+    let mut kept: Option<usize> = None;
+    let mut error: Option<f32> = None;
+
+    // Teaching mode adds one term on each cycle, over the traces of the previous cycles
+    let mut teaching = false;
+    let mut approximations: Vec<Vec<(f32, f32)>> = Vec::new();
+
+    // Follow camera on the pen, with the point it looks at trailing behind the pen
+    let mut following = false;
+    let mut zoom = FOLLOW_ZOOM;
+    let mut target = ORIGIN;
 
     let mut points: Vec<(f32, f32)> = Vec::new();
 
+    // Path being sketched with the mouse, and whether a drawn path plays instead of the list
+    let mut sketch: Vec<(f32, f32)> = Vec::new();
+    let mut drawn = false;
+
+    // Epicycles of a closed path, the centroid they turn around and the resampled path
+    let fourier = |path: &[(f32, f32)]| {
+        let count = path.len().next_power_of_two().clamp(MIN_POINTS, MAX_POINTS);
+        let path = drawing::resample(path, count);
+        (drawing::centroid(&path), drawing::epicycles(&path), path)
+    };
+
+    // An svg file plays like a drawn path
+    if let Some(path) = svg_path {
+        match svg::load(path) {
+            Ok(path) => {
+                (origin, trigos, source) =
+                    fourier(&svg::fit(&path, screen_width(), screen_height()));
+                drawn = true;
+            }
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
     let mut t = 0;
 
     loop {
         clear_background(BLACK);
 
+        // Drawing
         {
-            let mut pos = (400., 300.);
+            if is_mouse_button_down(MouseButton::Left) {
+                let mouse = mouse_position();
+                if sketch.last() != Some(&mouse) {
+                    sketch.push(mouse);
+                }
+            } else if sketch.len() >= 3 {
+                (origin, trigos, source) = fourier(&sketch);
+                error = None;
+                if teaching {
+                    kept = keep(1, trigos.len());
+                    approximations.clear();
+                }
+                drawn = true;
+                t = 0;
+                points = Vec::new();
+                sketch = Vec::new();
+            } else {
+                sketch.clear();
+            }
 
-            trigos.iter().for_each(|(m, tm)| {
-                let next_pos = (
-                    pos.0 + m * (tm * t as f32 * step).cos(),
-                    pos.1 + m * (tm * t as f32 * step).sin(),
-                );
-                draw_circle_lines(pos.0, pos.1, *m, 1., WHITE);
-                draw_line(pos.0, pos.1, next_pos.0, next_pos.1, 1., WHITE);
-                pos = next_pos;
-            });
-            points.push((pos.0, pos.1));
+            // Back to the list
+            if is_key_pressed(KeyCode::Space) && drawn {
+                origin = ORIGIN;
+                (trigos, source) = next_trigos();
+                error = None;
+                if teaching {
+                    kept = keep(1, trigos.len());
+                    approximations.clear();
+                }
+                drawn = false;
+                t = 0;
+                points = Vec::new();
+            }
         }
 
-        {
-            let mut points_iter = points.iter().peekable();
-            while let Some(point) = points_iter.next() {
-                if let Some(next_point) = points_iter.peek() {
+        if !sketch.is_empty() {
+            sketch
+                .iter()
+                .zip(sketch.iter().skip(1))
+                .for_each(|(point, next_point)| {
                     draw_line(point.0, point.1, next_point.0, next_point.1, 2., WHITE)
+                });
+            next_frame().await;
+            continue;
+        }
+
+        // Terms
+        {
+            let count = kept.unwrap_or(trigos.len());
+            let changed = if is_key_pressed(KeyCode::Up) {
+                Some(count + 1)
+            } else if is_key_pressed(KeyCode::Down) {
+                Some(count.saturating_sub(1).max(1))
+            } else if is_key_pressed(KeyCode::PageUp) {
+                Some(count * 2)
+            } else if is_key_pressed(KeyCode::PageDown) {
+                Some((count / 2).max(1))
+            } else if is_key_pressed(KeyCode::Home) {
+                Some(trigos.len())
+            } else {
+                None
+            };
+            if let Some(count) = changed {
+                kept = keep(count, trigos.len());
+                error = None;
+                points = Vec::new();
+            }
+
+            // Teaching mode starts over from the largest term
+            if is_key_pressed(KeyCode::T) {
+                teaching = !teaching;
+                kept = if teaching {
+                    keep(1, trigos.len())
+                } else {
+                    None
+                };
+                error = None;
+                approximations.clear();
+                t = 0;
+                points = Vec::new();
+            }
+        }
+        let terms = &trigos[..kept.unwrap_or(trigos.len()).min(trigos.len())];
+
+        {
+            // Pen positions between the previous frame and this one, enough to follow the
+            // fastest term
+            let fastest = terms
+                .iter()
+                .fold(0., |fastest: f32, term| fastest.max(term.frequency.abs()));
+            let substeps = ((2. * fastest / STEPS as f32).ceil() as usize).max(1);
+            if t > 0 {
+                (1..substeps).for_each(|s| {
+                    let time = (t as f32 - 1. + s as f32 / substeps as f32) * step;
+                    points.push(drawing::pen(terms, origin, time));
+                });
+            }
+
+            points.push(drawing::pen(terms, origin, t as f32 * step));
+        }
+
+        // Camera
+        {
+            let (width, height) = (screen_width(), screen_height());
+            let pen = *points.last().unwrap();
+
+            if is_key_pressed(KeyCode::F) {
+                following = !following;
+                target = pen;
+            }
+
+            if following {
+                let wheel = mouse_wheel().1;
+                if wheel > 0. {
+                    zoom = (zoom * ZOOM_FACTOR).min(MAX_ZOOM);
+                } else if wheel < 0. {
+                    zoom = (zoom / ZOOM_FACTOR).max(1.);
                 }
+
+                // The target catches up with the pen at the same pace whatever the frame rate
+                let catch_up = 1. - (-FOLLOW_RATE * get_frame_time()).exp();
+                target.0 += (pen.0 - target.0) * catch_up;
+                target.1 += (pen.1 - target.1) * catch_up;
+
+                let (view_width, view_height) = (width / zoom, height / zoom);
+                let view = Rect::new(
+                    target.0 - view_width / 2.,
+                    target.1 - view_height / 2.,
+                    view_width,
+                    view_height,
+                );
+                set_camera(&Camera2D::from_display_rect(view));
+                draw_scene(
+                    terms,
+                    origin,
+                    t as f32 * step,
+                    &points,
+                    &approximations,
+                    1. / zoom,
+                );
+
+                // Whole view in a corner, the viewport counting from the bottom of the window
+                let (inset_width, inset_height) = (width * INSET_SCALE, height * INSET_SCALE);
+                let (inset_x, inset_y) = (width - inset_width - INSET_MARGIN, INSET_MARGIN);
+                set_camera(&Camera2D {
+                    viewport: Some((
+                        inset_x as i32,
+                        (height - inset_y - inset_height) as i32,
+                        inset_width as i32,
+                        inset_height as i32,
+                    )),
+                    ..Camera2D::from_display_rect(Rect::new(0., 0., width, height))
+                });
+                let pixel = 1. / INSET_SCALE;
+                draw_rectangle(0., 0., width, height, BLACK);
+                draw_scene(
+                    terms,
+                    origin,
+                    t as f32 * step,
+                    &points,
+                    &approximations,
+                    pixel,
+                );
+                draw_rectangle_lines(view.x, view.y, view.w, view.h, 2. * pixel, YELLOW);
+
+                set_default_camera();
+                draw_rectangle_lines(inset_x, inset_y, inset_width, inset_height, 1., WHITE);
+            } else {
+                draw_scene(terms, origin, t as f32 * step, &points, &approximations, 1.);
+            }
+        }
+
+        // Error
+        {
+            let error = error.get_or_insert_with(|| drawing::rms_error(terms, origin, &source));
+            draw_text(
+                &format!(
+                    "terms = {} / {}, rms error = {:.2} px{}",
+                    terms.len(),
+                    trigos.len(),
+                    error,
+                    if teaching { " (teaching)" } else { "" }
+                ),
+                10.,
+                20.,
+                16.,
+                WHITE,
+            );
+            if following {
+                draw_text(
+                    &format!("following the pen, zoom = {:.1}", zoom),
+                    10.,
+                    40.,
+                    16.,
+                    WHITE,
+                );
             }
         }
 
         t += 1;
 
+        // A drawn path loops until another one is drawn, in teaching mode a set is left
+        // once it's drawn by all its terms
         if t == STEPS {
             t = 0;
-            points = Vec::new();
-            trigos = trigos_fns.next().unwrap();
+            let traced = std::mem::take(&mut points);
+            if let (true, Some(count)) = (teaching, kept) {
+                approximations.push(traced);
+                if approximations.len() > APPROXIMATIONS {
+                    approximations.remove(0);
+                }
+                kept = keep(count + 1, trigos.len());
+                error = None;
+            } else {
+                if !drawn {
+                    (trigos, source) = next_trigos();
+                }
+                if teaching {
+                    kept = keep(1, trigos.len());
+                    approximations.clear();
+                }
+                error = None;
+            }
         }
 
         next_frame().await;
@@ -0,0 +1,376 @@
+use std::f32::consts::TAU;
+use std::fs;
+use std::path::Path;
+
+use num_complex::Complex;
+
+// Lines of a flattened bezier curve
+const CURVE_SEGMENTS: usize = 16;
+// Lines of a flattened full turn of an elliptical arc
+const ARC_SEGMENTS: f32 = 64.;
+// Share of the window taken by the fitted drawing
+const FIT_MARGIN: f32 = 0.9;
+
+type Point = Complex<f32>;
+
+struct Parser<'a> {
+    data: &'a [u8],
+    i: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a str) -> Self {
+        Self {
+            data: data.as_bytes(),
+            i: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.i < self.data.len()
+            && (self.data[self.i].is_ascii_whitespace() || self.data[self.i] == b',')
+        {
+            self.i += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.i == self.data.len()
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let next = *self.data.get(self.i)?;
+        if next.is_ascii_alphabetic() {
+            self.i += 1;
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    fn has_number(&mut self) -> bool {
+        self.skip_separators();
+        matches!(
+            self.data.get(self.i),
+            Some(b'0'..=b'9' | b'.' | b'-' | b'+')
+        )
+    }
+
+    // Numbers can follow each other without separator, as in "1.5.5" or "2-3"
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_separators();
+        let start = self.i;
+        let digits = |parser: &mut Self| {
+            while matches!(parser.data.get(parser.i), Some(b'0'..=b'9')) {
+                parser.i += 1;
+            }
+        };
+
+        if matches!(self.data.get(self.i), Some(b'-' | b'+')) {
+            self.i += 1;
+        }
+        digits(self);
+        if self.data.get(self.i) == Some(&b'.') {
+            self.i += 1;
+            digits(self);
+        }
+        if matches!(self.data.get(self.i), Some(b'e' | b'E'))
+            && matches!(self.data.get(self.i + 1), Some(b'0'..=b'9' | b'-' | b'+'))
+        {
+            self.i += 2;
+            digits(self);
+        }
+
+        let text = std::str::from_utf8(&self.data[start..self.i]).unwrap();
+        text.parse()
+            .map_err(|_| format!("Invalid number at {} in path data", start))
+    }
+
+    fn point(&mut self) -> Result<Point, String> {
+        Ok(Complex::new(self.number()?, self.number()?))
+    }
+
+    // Arc flags are a single digit, possibly glued to the next number
+    fn flag(&mut self) -> Result<bool, String> {
+        self.skip_separators();
+        let flag = match self.data.get(self.i) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(format!("Invalid arc flag at {} in path data", self.i)),
+        };
+        self.i += 1;
+        Ok(flag)
+    }
+}
+
+fn cubic(from: Point, c1: Point, c2: Point, to: Point, line: &mut Vec<Point>) {
+    (1..=CURVE_SEGMENTS).for_each(|i| {
+        let t = i as f32 / CURVE_SEGMENTS as f32;
+        let u = 1. - t;
+        line.push(from * u * u * u + c1 * 3. * u * u * t + c2 * 3. * u * t * t + to * t * t * t);
+    });
+}
+
+fn quadratic(from: Point, control: Point, to: Point, line: &mut Vec<Point>) {
+    (1..=CURVE_SEGMENTS).for_each(|i| {
+        let t = i as f32 / CURVE_SEGMENTS as f32;
+        let u = 1. - t;
+        line.push(from * u * u + control * 2. * u * t + to * t * t);
+    });
+}
+
+// Elliptical arc from the endpoint parameters of the path data, converted to its center
+// and angles as in the implementation notes of the SVG specification
+fn arc(
+    from: Point,
+    radii: Point,
+    rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    to: Point,
+    line: &mut Vec<Point>,
+) {
+    let (mut rx, mut ry) = (radii.re.abs(), radii.im.abs());
+    if from == to {
+        return;
+    }
+    if rx == 0. || ry == 0. {
+        line.push(to);
+        return;
+    }
+
+    // Endpoints in the frame of the ellipse axes, centered between them
+    let axes = Complex::from_polar(1., rotation.to_radians());
+    let p = (from - to) / 2. / axes;
+
+    // Radii too small to join the endpoints are scaled up
+    let lambda = (p.re / rx).powi(2) + (p.im / ry).powi(2);
+    if lambda > 1. {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let (rx2, ry2) = (rx * rx, ry * ry);
+    let numerator = rx2 * ry2 - rx2 * p.im * p.im - ry2 * p.re * p.re;
+    let denominator = rx2 * p.im * p.im + ry2 * p.re * p.re;
+    let sign = if large_arc == sweep { -1. } else { 1. };
+    let k = sign * (numerator / denominator).max(0.).sqrt();
+    let center = Complex::new(k * rx * p.im / ry, -k * ry * p.re / rx);
+
+    let angle = |v: Point| v.im.atan2(v.re);
+    let start = angle(Complex::new(
+        (p.re - center.re) / rx,
+        (p.im - center.im) / ry,
+    ));
+    let end = angle(Complex::new(
+        (-p.re - center.re) / rx,
+        (-p.im - center.im) / ry,
+    ));
+    let mut delta = (end - start) % TAU;
+    if sweep && delta < 0. {
+        delta += TAU;
+    } else if !sweep && delta > 0. {
+        delta -= TAU;
+    }
+
+    let center = center * axes + (from + to) / 2.;
+    let segments = (delta.abs() / TAU * ARC_SEGMENTS).ceil().max(1.) as usize;
+    (1..=segments).for_each(|i| {
+        let theta = start + delta * i as f32 / segments as f32;
+        line.push(center + Complex::new(rx * theta.cos(), ry * theta.sin()) * axes);
+    });
+}
+
+// Polylines of the subpaths of path data
+fn parse(data: &str) -> Result<Vec<Vec<Point>>, String> {
+    let mut parser = Parser::new(data);
+    let mut subpaths = Vec::new();
+    let mut line: Vec<Point> = Vec::new();
+
+    let (mut position, mut start) = (Complex::new(0., 0.), Complex::new(0., 0.));
+    // Last control point of a cubic or quadratic curve, reflected by S and T
+    let (mut cubic_control, mut quadratic_control): (Option<Point>, Option<Point>) = (None, None);
+    let mut command = None;
+
+    while !parser.at_end() {
+        // A command letter can be left out to repeat the previous command
+        if let Some(letter) = parser.command() {
+            command = Some(letter);
+        } else if !parser.has_number() || command.is_none() {
+            return Err(format!("Unexpected character at {} in path data", parser.i));
+        }
+        let letter = command.unwrap();
+
+        let origin = if letter.is_ascii_lowercase() {
+            position
+        } else {
+            Complex::new(0., 0.)
+        };
+        let (mut next_cubic, mut next_quadratic) = (None, None);
+
+        let uppercase = letter.to_ascii_uppercase();
+        match uppercase {
+            b'M' => {
+                position = origin + parser.point()?;
+                start = position;
+                if line.len() > 1 {
+                    subpaths.push(line);
+                }
+                line = vec![position];
+                // Following pairs are lines
+                command = Some(if letter == b'm' { b'l' } else { b'L' });
+            }
+            b'L' => {
+                position = origin + parser.point()?;
+                line.push(position);
+            }
+            b'H' => {
+                position.re = origin.re + parser.number()?;
+                line.push(position);
+            }
+            b'V' => {
+                position.im = origin.im + parser.number()?;
+                line.push(position);
+            }
+            b'C' | b'S' => {
+                let c1 = if uppercase == b'C' {
+                    origin + parser.point()?
+                } else {
+                    cubic_control.map_or(position, |control| position * 2. - control)
+                };
+                let c2 = origin + parser.point()?;
+                let to = origin + parser.point()?;
+                cubic(position, c1, c2, to, &mut line);
+                next_cubic = Some(c2);
+                position = to;
+            }
+            b'Q' | b'T' => {
+                let control = if uppercase == b'Q' {
+                    origin + parser.point()?
+                } else {
+                    quadratic_control.map_or(position, |control| position * 2. - control)
+                };
+                let to = origin + parser.point()?;
+                quadratic(position, control, to, &mut line);
+                next_quadratic = Some(control);
+                position = to;
+            }
+            b'A' => {
+                let radii = parser.point()?;
+                let rotation = parser.number()?;
+                let (large_arc, sweep) = (parser.flag()?, parser.flag()?);
+                let to = origin + parser.point()?;
+                arc(position, radii, rotation, large_arc, sweep, to, &mut line);
+                position = to;
+            }
+            b'Z' => {
+                line.push(start);
+                position = start;
+                subpaths.push(line);
+                line = vec![start];
+                // Z takes no numbers
+                command = None;
+            }
+            _ => return Err(format!("Unknown command {} in path data", letter as char)),
+        }
+        (cubic_control, quadratic_control) = (next_cubic, next_quadratic);
+    }
+
+    if line.len() > 1 {
+        subpaths.push(line);
+    }
+    Ok(subpaths)
+}
+
+// Values of the d attributes of the path elements, their transforms are not applied
+fn path_data(svg: &str) -> Vec<&str> {
+    svg.match_indices("<path")
+        .filter_map(|(start, _)| {
+            let tag = &svg[start..start + svg[start..].find('>')?];
+            let attribute = tag
+                .match_indices("d=")
+                .find(|&(i, _)| tag.as_bytes()[i - 1].is_ascii_whitespace())?
+                .0;
+            let value = &tag[attribute + 2..];
+            let quote = value.chars().next()?;
+            let end = value[1..].find(quote)?;
+            Some(&value[1..end + 1])
+        })
+        .collect()
+}
+
+// One path through every subpath, each time going on with the one starting or ending
+// the closest to where the pen is, reversed in the latter case
+fn join(mut subpaths: Vec<Vec<Point>>) -> Vec<Point> {
+    let mut path = subpaths.remove(0);
+
+    while !subpaths.is_empty() {
+        let pen = *path.last().unwrap();
+        let (index, reversed, _) = subpaths
+            .iter()
+            .enumerate()
+            .flat_map(|(i, subpath)| {
+                [
+                    (i, false, (subpath[0] - pen).norm()),
+                    (i, true, (subpath[subpath.len() - 1] - pen).norm()),
+                ]
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .unwrap();
+
+        let mut subpath = subpaths.remove(index);
+        if reversed {
+            subpath.reverse();
+        }
+        path.extend(subpath);
+    }
+    path
+}
+
+// Single path through every path element of an svg file
+pub fn load(path: &Path) -> Result<Vec<(f32, f32)>, String> {
+    let svg = fs::read_to_string(path)
+        .map_err(|err| format!("Couldn't read {}: {}", path.display(), err))?;
+
+    let mut subpaths = Vec::new();
+    for data in path_data(&svg) {
+        subpaths.extend(parse(data).map_err(|err| format!("{}: {}", path.display(), err))?);
+    }
+    if subpaths.is_empty() {
+        return Err(format!("No path in {}", path.display()));
+    }
+
+    Ok(join(subpaths)
+        .into_iter()
+        .map(|point| (point.re, point.im))
+        .collect())
+}
+
+// Points scaled and moved to fill the window, keeping their proportions
+pub fn fit(points: &[(f32, f32)], width: f32, height: f32) -> Vec<(f32, f32)> {
+    let (min_x, max_x, min_y, max_y) = points.iter().fold(
+        (
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ),
+        |(min_x, max_x, min_y, max_y), &(x, y)| {
+            (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+        },
+    );
+    let scale = FIT_MARGIN * (width / (max_x - min_x)).min(height / (max_y - min_y));
+    let scale = if scale.is_finite() { scale } else { 1. };
+
+    points
+        .iter()
+        .map(|&(x, y)| {
+            (
+                width / 2. + (x - (min_x + max_x) / 2.) * scale,
+                height / 2. + (y - (min_y + max_y) / 2.) * scale,
+            )
+        })
+        .collect()
+}
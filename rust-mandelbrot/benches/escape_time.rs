@@ -0,0 +1,76 @@
+use std::hint::black_box;
+use std::sync::atomic::AtomicBool;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use num_complex::Complex;
+
+use rust_mandelbrot::deep::BigComplex;
+use rust_mandelbrot::formula::formula;
+use rust_mandelbrot::fractal::{self, Sampler};
+use rust_mandelbrot::viewport::Viewport;
+
+const SIZE: u32 = 256;
+const ITERATIONS: u32 = 500;
+
+fn viewport(re: f64, im: f64, scale: f64) -> Viewport {
+    Viewport {
+        center: BigComplex::new(re, im),
+        scale,
+        width: SIZE,
+        height: SIZE,
+    }
+}
+
+// Every row of the viewport, the way the renderer hands them to the sampler
+fn rows(sampler: &Sampler, scalar: bool) {
+    (0..SIZE).for_each(|y| {
+        let points: Vec<(f64, f64)> = (0..SIZE).map(|x| (x as f64, y as f64)).collect();
+        black_box(if scalar {
+            sampler.scalar_samples(&points)
+        } else {
+            sampler.samples(&points)
+        });
+    });
+}
+
+fn escape_time(criterion: &mut Criterion) {
+    let mandelbrot = formula("Mandelbrot", 2).unwrap();
+    let running = AtomicBool::new(false);
+    let views = [
+        (
+            "whole set",
+            fractal::mandelbrot(&viewport(-0.5, 0., 4.), &mandelbrot, ITERATIONS, &running)
+                .unwrap(),
+        ),
+        (
+            "seahorse valley",
+            fractal::mandelbrot(
+                &viewport(-0.7436, 0.1318, 0.01),
+                &mandelbrot,
+                ITERATIONS,
+                &running,
+            )
+            .unwrap(),
+        ),
+        (
+            "dendrite julia",
+            fractal::julia(
+                &viewport(0., 0., 3.),
+                &mandelbrot,
+                Complex::new(0., 1.),
+                ITERATIONS,
+            ),
+        ),
+    ];
+
+    for (name, sampler) in &views {
+        let mut group = criterion.benchmark_group(*name);
+        group.sample_size(20);
+        group.bench_function("scalar", |bencher| bencher.iter(|| rows(sampler, true)));
+        group.bench_function("lanes", |bencher| bencher.iter(|| rows(sampler, false)));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, escape_time);
+criterion_main!(benches);
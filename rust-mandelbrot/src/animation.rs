@@ -0,0 +1,185 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+use num_complex::Complex;
+use serde::Deserialize;
+
+use crate::deep::{precision, BigComplex};
+use crate::headless::{coloring, parse_options, render_image, sampler, save_png, USAGE};
+use crate::viewport::{check_scale, Viewport};
+
+// Color quantization effort of the gif frames, from 1 (best) to 30 (fastest)
+const GIF_SPEED: i32 = 10;
+
+#[derive(Deserialize)]
+struct KeyframeEntry {
+    // Seconds from the start of the animation
+    time: f64,
+    center: (String, String),
+    scale: f64,
+    #[serde(default)]
+    julia: Option<(f64, f64)>,
+}
+
+#[derive(Deserialize)]
+struct KeyframeFile {
+    keyframe: Vec<KeyframeEntry>,
+}
+
+struct Keyframe {
+    time: f64,
+    center: BigComplex,
+    scale: f64,
+    julia: Option<Complex<f64>>,
+}
+
+fn load_keyframes(path: &str) -> Result<Vec<Keyframe>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|err| format!("Couldn't read {}: {}", path, err))?;
+    let file: KeyframeFile =
+        toml::from_str(&content).map_err(|err| format!("Couldn't load {}: {}", path, err))?;
+
+    let mut keyframes = file
+        .keyframe
+        .into_iter()
+        .map(|entry| {
+            Ok(Keyframe {
+                time: entry.time,
+                center: BigComplex::parse(&entry.center.0, &entry.center.1)?,
+                scale: check_scale(entry.scale)?,
+                julia: entry.julia.map(|(re, im)| Complex::new(re, im)),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    if keyframes.is_empty() {
+        return Err(format!("No keyframe in {}", path));
+    }
+    Ok(keyframes)
+}
+
+// Smoothstep of u and its complement, both computed directly so neither loses precision near 0
+fn ease(u: f64) -> (f64, f64) {
+    (u * u * (3. - 2. * u), (1. - u) * (1. - u) * (1. + 2. * u))
+}
+
+// View at u between two keyframes: the zoom is interpolated in log scale with an eased
+// progress, and the center moves along with the scale so a zoom target stays in the frame
+fn interpolate(from: &Keyframe, to: &Keyframe, u: f64, pixels: u32) -> (BigComplex, f64) {
+    let (progress, remaining) = ease(u);
+    let zoom = (to.scale / from.scale).ln();
+    let scale = from.scale * (progress * zoom).exp();
+
+    // Share of the way covered, p + q = 1
+    let (p, q) = if zoom.abs() < 1e-9 {
+        (progress, remaining)
+    } else {
+        let span = to.scale - from.scale;
+        (
+            from.scale * (progress * zoom).exp_m1() / span,
+            -to.scale * (-remaining * zoom).exp_m1() / span,
+        )
+    };
+
+    // Offsets from the closest keyframe, small enough for the f64 precision at that depth
+    let delta = to.center.difference(&from.center);
+    let precision = precision(scale / pixels as f64);
+    let center = if p < 0.5 {
+        from.center.translate(delta * p, precision)
+    } else {
+        to.center.translate(-delta * q, precision)
+    };
+    (center, scale)
+}
+
+fn julia(from: &Keyframe, to: &Keyframe, u: f64) -> Option<Complex<f64>> {
+    match (from.julia, to.julia) {
+        (Some(a), Some(b)) => Some(a + (b - a) * ease(u).0),
+        (a, b) => a.or(b),
+    }
+}
+
+pub fn animate(args: &[String]) -> Result<(), String> {
+    let options = parse_options(args)?;
+    let output = options
+        .output
+        .clone()
+        .ok_or_else(|| format!("Missing output file or directory\n\n{}", USAGE))?;
+    let keyframes = load_keyframes(
+        options
+            .keyframes
+            .as_ref()
+            .ok_or_else(|| format!("Missing keyframes file\n\n{}", USAGE))?,
+    )?;
+    if options.fps <= 0. {
+        return Err(format!("Invalid frame rate {}", options.fps));
+    }
+
+    let coloring = coloring(&options)?;
+    let (width, height) = options.size;
+    let (start, end) = (keyframes[0].time, keyframes[keyframes.len() - 1].time);
+    let frames = ((end - start) * options.fps).round() as u32 + 1;
+
+    let mut gif = if output.to_lowercase().ends_with(".gif") {
+        let file =
+            File::create(&output).map_err(|err| format!("Couldn't create {}: {}", output, err))?;
+        let mut encoder = gif::Encoder::new(BufWriter::new(file), width as u16, height as u16, &[])
+            .map_err(|err| err.to_string())?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|err| err.to_string())?;
+        Some(encoder)
+    } else {
+        fs::create_dir_all(&output)
+            .map_err(|err| format!("Couldn't create {}: {}", output, err))?;
+        None
+    };
+
+    for frame in 0..frames {
+        let time = (start + frame as f64 / options.fps).min(end);
+        let next = keyframes
+            .iter()
+            .position(|keyframe| keyframe.time > time)
+            .unwrap_or(keyframes.len() - 1)
+            .max(1)
+            .min(keyframes.len() - 1);
+        let (from, to) = (&keyframes[next.saturating_sub(1)], &keyframes[next]);
+        let u = if to.time > from.time {
+            ((time - from.time) / (to.time - from.time)).clamp(0., 1.)
+        } else {
+            1.
+        };
+
+        let (center, scale) = interpolate(from, to, u, width.min(height));
+        let viewport = Viewport {
+            center,
+            scale,
+            width,
+            height,
+        };
+        // Keyframes without julia or nova parameter keep the one of the options
+        let sampler = sampler(&options, &viewport, julia(from, to, u).or(options.julia))?;
+
+        match &mut gif {
+            Some(encoder) => {
+                let rgb = render_image(&viewport, &sampler, &coloring, options.antialiasing);
+                let mut image =
+                    gif::Frame::from_rgb_speed(width as u16, height as u16, &rgb, GIF_SPEED);
+                image.delay = (100. / options.fps).round() as u16;
+                encoder.write_frame(&image).map_err(|err| err.to_string())?;
+            }
+            None => save_png(
+                &format!("{}/frame_{:05}.png", output, frame),
+                &viewport,
+                &sampler,
+                &coloring,
+                options.antialiasing,
+            )?,
+        }
+        eprint!("\rFrame {} / {}", frame + 1, frames);
+    }
+    eprintln!();
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::fs;
+
+use num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::deep::BigComplex;
+use crate::viewport::check_scale;
+
+pub const BOOKMARKS_FILE: &str = "bookmarks.toml";
+
+fn default_degree() -> u32 {
+    3
+}
+
+// A saved view, the center is kept in decimal to hold every digit of deep zooms
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub center: (String, String),
+    pub scale: f64,
+    // None follows the zoom depth
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<u32>,
+    pub formula: String,
+    #[serde(default = "default_degree")]
+    pub degree: u32,
+    pub palette: String,
+    // Pinned parameter of the julia pane
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub julia: Option<(f64, f64)>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct BookmarkFile {
+    #[serde(default)]
+    bookmark: Vec<Bookmark>,
+}
+
+impl Bookmark {
+    pub fn center(&self) -> Result<BigComplex, String> {
+        BigComplex::parse(&self.center.0, &self.center.1)
+    }
+
+    pub fn julia(&self) -> Option<Complex<f64>> {
+        self.julia.map(|(re, im)| Complex::new(re, im))
+    }
+
+    // Bookmarks of the file, none when there is no file yet
+    // Views that can be rendered, shared by every way of reading a bookmark
+    pub fn check(&self) -> Result<(), String> {
+        check_scale(self.scale)?;
+        self.center()?;
+        Ok(())
+    }
+
+    pub fn load_all(path: &str) -> Vec<Bookmark> {
+        match fs::read_to_string(path) {
+            Ok(content) => match toml::from_str::<BookmarkFile>(&content) {
+                // Hand edited entries can hold views that can't be shown
+                Ok(file) => file
+                    .bookmark
+                    .into_iter()
+                    .filter(|bookmark| match bookmark.check() {
+                        Ok(()) => true,
+                        Err(err) => {
+                            eprintln!("Skipped bookmark {} in {}: {}", bookmark.name, path, err);
+                            false
+                        }
+                    })
+                    .collect(),
+                Err(err) => {
+                    eprintln!("Couldn't load {}: {}", path, err);
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn save_all(path: &str, bookmarks: &[Bookmark]) -> Result<(), String> {
+        let content = toml::to_string(&BookmarkFile {
+            bookmark: bookmarks.to_vec(),
+        })
+        .map_err(|err| err.to_string())?;
+        fs::write(path, content).map_err(|err| format!("Couldn't save {}: {}", path, err))
+    }
+
+    // One line form to share a view, "x=-0.75;y=0.1;s=0.01;f=Mandelbrot;d=3;p=Hue",
+    // followed by ";i=500" for a manual iteration count and ";j=-0.8,0.156" for a pinned julia
+    pub fn to_share(&self) -> String {
+        let mut share = format!(
+            "x={};y={};s={:e};f={};d={};p={}",
+            self.center.0, self.center.1, self.scale, self.formula, self.degree, self.palette
+        );
+        if let Some(iterations) = self.iterations {
+            share += &format!(";i={}", iterations);
+        }
+        if let Some((re, im)) = self.julia {
+            share += &format!(";j={},{}", re, im);
+        }
+        share
+    }
+
+    pub fn from_share(share: &str) -> Result<Bookmark, String> {
+        let mut bookmark = Bookmark {
+            name: "Shared".to_string(),
+            center: ("0".to_string(), "0".to_string()),
+            scale: 0.,
+            iterations: None,
+            formula: "Mandelbrot".to_string(),
+            degree: default_degree(),
+            palette: "Hue".to_string(),
+            julia: None,
+        };
+
+        let number = |value: &str| -> Result<f64, String> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid number {}", value))
+        };
+        for field in share.trim().split(';').filter(|field| !field.is_empty()) {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid field {}", field))?;
+            match key.trim() {
+                "x" => bookmark.center.0 = value.trim().to_string(),
+                "y" => bookmark.center.1 = value.trim().to_string(),
+                "s" => bookmark.scale = number(value)?,
+                "f" => bookmark.formula = value.trim().to_string(),
+                "d" => bookmark.degree = number(value)? as u32,
+                "p" => bookmark.palette = value.trim().to_string(),
+                "i" => bookmark.iterations = Some(number(value)? as u32),
+                "j" => {
+                    let (re, im) = value
+                        .split_once(',')
+                        .ok_or_else(|| format!("Invalid julia parameter {}", value))?;
+                    bookmark.julia = Some((number(re)?, number(im)?));
+                }
+                _ => return Err(format!("Unknown field {}", key)),
+            }
+        }
+
+        if bookmark.scale == 0. {
+            return Err("Missing scale".to_string());
+        }
+        bookmark.check()?;
+        Ok(bookmark)
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use ::rand::{rngs::SmallRng, Rng, SeedableRng};
+use macroquad::prelude::*;
+use num_complex::Complex;
+use rayon::prelude::*;
+
+use crate::formula::Formula;
+use crate::fractal::BAILOUT;
+use crate::viewport::Viewport;
+
+// Iteration limit of each RGB channel relative to the pane iterations, nebulabrot style
+const CHANNEL_LIMITS: [u32; 3] = [100, 10, 1];
+// The parameters are drawn from the square of this half size around 0
+const SAMPLE_RADIUS: f64 = 2.;
+// Share of the parameters drawn anywhere in the square rather than next to the last one
+// whose orbit crossed the view, so that no part of the view is left out
+const JUMP_PROBABILITY: f64 = 0.2;
+// Half size of the steps between neighbouring parameters, relative to the view scale
+const STEP_RADIUS: f64 = 0.1;
+// Parameters whose orbits are traced by each task of a batch
+const TASK_SAMPLES: usize = 2000;
+#[cfg(not(target_arch = "wasm32"))]
+const BATCH_TASKS: usize = 64;
+// Seconds between two tone mappings of the accumulated counts
+const REFRESH_INTERVAL: f64 = 0.25;
+// Brightness curve of the tone mapping, below 1 lifts the faint orbits
+const GAMMA: f32 = 0.5;
+// Seconds of each frame spent tracing where there are no threads to do it
+#[cfg(target_arch = "wasm32")]
+const FRAME_BUDGET: f64 = 0.01;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Density {
+    // Orbits of the escaping parameters
+    Buddhabrot,
+    // Orbits of the parameters still bounded at the limit
+    AntiBuddhabrot,
+}
+
+impl Density {
+    pub fn name(&self) -> &str {
+        match self {
+            Density::Buddhabrot => "Nebulabrot",
+            Density::AntiBuddhabrot => "Anti-Buddhabrot",
+        }
+    }
+}
+
+// Iteration limits of the red, green and blue channels
+pub fn channel_limits(iterations: u32) -> [u32; 3] {
+    CHANNEL_LIMITS.map(|ratio| iterations.saturating_mul(ratio))
+}
+
+// Orbits traced from the main loop, on targets without threads
+#[cfg(target_arch = "wasm32")]
+struct Job {
+    viewport: Viewport,
+    formula: Arc<dyn Formula>,
+    density: Density,
+    limits: [u32; 3],
+}
+
+// Accumulates orbit densities on a background worker, the counts are kept for as
+// long as the view doesn't change and tone mapped into the texture from the main loop
+pub struct Buddhabrot {
+    texture: Texture2D,
+    width: u32,
+    height: u32,
+    // Hits of the red, green and blue orbits, interleaved per pixel
+    counts: Arc<Vec<AtomicU32>>,
+    samples: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
+    last_refresh: f64,
+    // Without threads, the orbits are traced a batch at a time on every update
+    #[cfg(target_arch = "wasm32")]
+    job: Option<Job>,
+}
+
+impl Buddhabrot {
+    pub fn new() -> Self {
+        Self {
+            texture: Texture2D::empty(),
+            width: 0,
+            height: 0,
+            counts: Arc::new(Vec::new()),
+            samples: Arc::new(AtomicU64::new(0)),
+            running: Arc::new(AtomicBool::new(false)),
+            last_refresh: 0.,
+            #[cfg(target_arch = "wasm32")]
+            job: None,
+        }
+    }
+
+    pub fn texture(&self) -> Texture2D {
+        self.texture
+    }
+
+    // Number of parameters traced so far
+    pub fn samples(&self) -> u64 {
+        self.samples.load(Ordering::Relaxed)
+    }
+
+    // Start accumulating a new image, the previous worker stops after its current batch
+    pub fn start(
+        &mut self,
+        viewport: &Viewport,
+        formula: &Arc<dyn Formula>,
+        density: Density,
+        limits: [u32; 3],
+    ) {
+        self.stop();
+
+        let (width, height) = (viewport.width, viewport.height);
+        if (width, height) != (self.width, self.height) {
+            if self.width * self.height != 0 {
+                self.texture.delete();
+            }
+            self.texture = Texture2D::from_rgba8(
+                width as u16,
+                height as u16,
+                &vec![0; (width * height * 4) as usize],
+            );
+            self.width = width;
+            self.height = height;
+        }
+
+        self.counts = Arc::new((0..width * height * 3).map(|_| AtomicU32::new(0)).collect());
+        self.samples = Arc::new(AtomicU64::new(0));
+        self.running = Arc::new(AtomicBool::new(true));
+        self.last_refresh = 0.;
+
+        let viewport = viewport.clone();
+        let formula = formula.clone();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (counts, samples, running) = (
+                self.counts.clone(),
+                self.samples.clone(),
+                self.running.clone(),
+            );
+            rayon::spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    (0..BATCH_TASKS)
+                        .into_par_iter()
+                        .for_each(|_| trace(&viewport, formula.as_ref(), density, limits, &counts));
+                    samples.fetch_add((BATCH_TASKS * TASK_SAMPLES) as u64, Ordering::Relaxed);
+                }
+            });
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.job = Some(Job {
+                viewport,
+                formula,
+                density,
+                limits,
+            });
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.job = None;
+        }
+    }
+
+    // Trace tasks for a share of the frame, on targets without threads
+    #[cfg(target_arch = "wasm32")]
+    fn work(&mut self) {
+        if let Some(job) = &self.job {
+            let start = get_time();
+            while get_time() - start < FRAME_BUDGET {
+                trace(
+                    &job.viewport,
+                    job.formula.as_ref(),
+                    job.density,
+                    job.limits,
+                    &self.counts,
+                );
+                self.samples
+                    .fetch_add(TASK_SAMPLES as u64, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Tone map the counts into the texture, every REFRESH_INTERVAL seconds
+    pub fn update(&mut self, exposure: f32) {
+        #[cfg(target_arch = "wasm32")]
+        self.work();
+
+        if self.width * self.height == 0 || get_time() - self.last_refresh < REFRESH_INTERVAL {
+            return;
+        }
+        self.last_refresh = get_time();
+
+        // Each channel is normalized by its own brightest pixel
+        let max = self
+            .counts
+            .par_chunks(3)
+            .map(|pixel| [0, 1, 2].map(|i| pixel[i].load(Ordering::Relaxed)))
+            .reduce(|| [1; 3], |a, b| [0, 1, 2].map(|i| a[i].max(b[i])))
+            .map(|count| count as f32);
+
+        self.texture.update(&Image {
+            bytes: self
+                .counts
+                .par_chunks(3)
+                .flat_map_iter(|pixel| {
+                    let channel = |i: usize| {
+                        let value = pixel[i].load(Ordering::Relaxed) as f32 / max[i] * exposure;
+                        (value.min(1.).powf(GAMMA) * 255.) as u8
+                    };
+                    [channel(0), channel(1), channel(2), 255]
+                })
+                .collect(),
+            width: self.width as u16,
+            height: self.height as u16,
+        });
+    }
+}
+
+impl Drop for Buddhabrot {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Parameter whose orbit crosses the view, with the pixels of its orbit points
+struct Hits {
+    c: Complex<f64>,
+    // Pixel index and iteration of each orbit point inside the view
+    pixels: Vec<(u32, u32)>,
+    // Iterations counted by the red, green and blue channels
+    lengths: [u32; 3],
+}
+
+impl Hits {
+    fn add(&self, counts: &[AtomicU32]) {
+        (0..3).for_each(|channel| {
+            self.pixels
+                .iter()
+                .filter(|&&(_, i)| i < self.lengths[channel])
+                .for_each(|&(pixel, _)| {
+                    counts[(pixel * 3) as usize + channel].fetch_add(1, Ordering::Relaxed);
+                });
+        });
+    }
+}
+
+// Orbit points of c in the view, none when no channel counts any of them
+fn view_hits(
+    viewport: &Viewport,
+    formula: &dyn Formula,
+    density: Density,
+    limits: [u32; 3],
+    c: Complex<f64>,
+    orbit: &mut Vec<Complex<f64>>,
+) -> Option<Hits> {
+    // Known interior parameters never escape, their orbits only matter to the anti-buddhabrot
+    if density == Density::Buddhabrot && formula.interior(c).is_some() {
+        return None;
+    }
+    let limit = limits.into_iter().max().unwrap_or(0);
+
+    orbit.clear();
+    let mut z = Complex::new(0., 0.);
+    let mut escaped = None;
+    for i in 0..limit {
+        z = formula.step(z, c);
+        if z.norm_sqr() >= BAILOUT * BAILOUT {
+            escaped = Some(i);
+            break;
+        }
+        orbit.push(z);
+    }
+
+    let lengths = limits.map(|channel_limit| match (density, escaped) {
+        (Density::Buddhabrot, Some(i)) if i < channel_limit => i,
+        (Density::AntiBuddhabrot, None) => channel_limit,
+        (Density::AntiBuddhabrot, Some(i)) if i >= channel_limit => channel_limit,
+        _ => 0,
+    });
+    let length = lengths.into_iter().max().unwrap_or(0) as usize;
+
+    let pixels: Vec<(u32, u32)> = orbit[..length]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, z)| {
+            let (x, y) = viewport.pixel(*z);
+            viewport
+                .contains(x, y)
+                .then_some((y as u32 * viewport.width + x as u32, i as u32))
+        })
+        .collect();
+    (!pixels.is_empty()).then_some(Hits { c, pixels, lengths })
+}
+
+// Trace the orbits of TASK_SAMPLES random parameters into the counts. Once zoomed in, few
+// parameters of the square have orbits crossing the view, so the next parameter is mostly
+// a small step away from the last one that did. Parameters whose orbits miss the view are
+// rejected and the last orbit is counted again, which keeps every crossing parameter as
+// likely as with uniform draws (a Metropolis walk over them)
+fn trace(
+    viewport: &Viewport,
+    formula: &dyn Formula,
+    density: Density,
+    limits: [u32; 3],
+    counts: &[AtomicU32],
+) {
+    // Each task draws its own seed from the generator of macroquad
+    let seed = (rand::rand() as u64) << 32 | rand::rand() as u64;
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let step = viewport.scale * STEP_RADIUS;
+    let mut orbit = Vec::new();
+    let mut current: Option<Hits> = None;
+
+    for _ in 0..TASK_SAMPLES {
+        let c = match &current {
+            Some(hits) if !rng.gen_bool(JUMP_PROBABILITY) => {
+                hits.c + Complex::new(rng.gen_range(-step..step), rng.gen_range(-step..step))
+            }
+            _ => Complex::new(
+                rng.gen_range(-SAMPLE_RADIUS..SAMPLE_RADIUS),
+                rng.gen_range(-SAMPLE_RADIUS..SAMPLE_RADIUS),
+            ),
+        };
+
+        // Steps leaving the square are rejected as well
+        let inside = c.re.abs() < SAMPLE_RADIUS && c.im.abs() < SAMPLE_RADIUS;
+        if let Some(hits) = inside
+            .then(|| view_hits(viewport, formula, density, limits, c, &mut orbit))
+            .flatten()
+        {
+            current = Some(hits);
+        }
+        if let Some(hits) = &current {
+            hits.add(counts);
+        }
+    }
+}
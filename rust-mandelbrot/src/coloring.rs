@@ -0,0 +1,405 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::fractal::{Interior, Sample, Surface};
+
+pub const PALETTES_FILE: &str = "palettes.toml";
+
+// Number of iterations covered by one palette cycle at density 1
+const PALETTE_PERIOD: f32 = 50.;
+// Newton steps halving the brightness of a basin at density 1
+const SHADE_STEPS: f32 = 10.;
+// Trap distance divided by e^TRAP_PERIOD over one palette cycle at density 1
+const TRAP_PERIOD: f32 = 4.;
+// Trap distance under which every point gets the same color
+const TRAP_MIN_DISTANCE: f32 = 1e-6;
+// Interior |z| covered by one palette cycle at density 1
+const MAGNITUDE_PERIOD: f32 = 2.;
+// Palette shift between consecutive periods, the golden ratio keeps them apart
+const PERIOD_STEP: f32 = 0.618034;
+
+// Lighting of the distance estimated surface, lit from the top left
+const LIGHT: [f32; 3] = [-0.5, -0.5, std::f32::consts::FRAC_1_SQRT_2];
+// Vertical part of the surface normals, lower values give steeper reliefs
+const HEIGHT: f32 = 1.;
+const AMBIENT: f32 = 0.3;
+const DIFFUSE: f32 = 0.7;
+const SPECULAR: f32 = 0.4;
+const SHININESS: f32 = 20.;
+// Width in pixels of the boundary lines
+const BOUNDARY_WIDTH: f32 = 1.;
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub [u8; 3]);
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        let digits = hex.trim_start_matches('#');
+        // Slicing by bytes needs single byte characters
+        if digits.len() != 6 || !digits.is_ascii() {
+            return Err(format!("Invalid color {}", hex));
+        }
+        let channel = |i: usize| {
+            u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+                .map_err(|_| format!("Invalid color {}", hex))
+        };
+        Ok(Rgb([channel(0)?, channel(1)?, channel(2)?]))
+    }
+}
+
+#[derive(Clone, Deserialize)]
+struct Stop {
+    position: f32,
+    color: Rgb,
+}
+
+#[derive(Deserialize)]
+struct PaletteFile {
+    palette: Vec<Palette>,
+}
+
+// A cyclic gradient, the last stop blends back into the first one
+#[derive(Clone, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    stops: Vec<Stop>,
+    #[serde(default = "default_interior")]
+    interior: Rgb,
+}
+
+fn default_interior() -> Rgb {
+    Rgb([0, 0, 0])
+}
+
+impl Palette {
+    fn new(name: &str, stops: &[(f32, [u8; 3])]) -> Self {
+        Self {
+            name: name.to_string(),
+            stops: stops
+                .iter()
+                .map(|&(position, color)| Stop {
+                    position,
+                    color: Rgb(color),
+                })
+                .collect(),
+            interior: default_interior(),
+        }
+    }
+
+    pub fn builtins() -> Vec<Palette> {
+        vec![
+            Palette::new(
+                "Hue",
+                &[
+                    (0., [255, 0, 0]),
+                    (1. / 6., [255, 255, 0]),
+                    (2. / 6., [0, 255, 0]),
+                    (3. / 6., [0, 255, 255]),
+                    (4. / 6., [0, 0, 255]),
+                    (5. / 6., [255, 0, 255]),
+                ],
+            ),
+            Palette::new(
+                "Classic",
+                &[
+                    (0., [0, 7, 100]),
+                    (0.16, [32, 107, 203]),
+                    (0.42, [237, 255, 255]),
+                    (0.6425, [255, 170, 0]),
+                    (0.8575, [0, 2, 0]),
+                ],
+            ),
+            Palette::new(
+                "Fire",
+                &[
+                    (0., [0, 0, 0]),
+                    (0.3, [180, 20, 0]),
+                    (0.6, [255, 160, 0]),
+                    (0.8, [255, 255, 200]),
+                ],
+            ),
+            Palette::new("Grayscale", &[(0., [0, 0, 0]), (0.5, [255, 255, 255])]),
+        ]
+    }
+
+    // Built-in palettes followed by the ones of the palettes file, if there is one
+    pub fn load_all(path: &str) -> Vec<Palette> {
+        let mut palettes = Palette::builtins();
+
+        if let Ok(content) = fs::read_to_string(path) {
+            match toml::from_str::<PaletteFile>(&content) {
+                Ok(file) => palettes.extend(
+                    file.palette
+                        .into_iter()
+                        .filter(|palette| !palette.stops.is_empty())
+                        .map(|mut palette| {
+                            palette
+                                .stops
+                                .sort_by(|a, b| a.position.total_cmp(&b.position));
+                            palette
+                        }),
+                ),
+                Err(err) => eprintln!("Couldn't load {}: {}", path, err),
+            }
+        }
+
+        palettes
+    }
+
+    // Color at position t, the gradient repeats every unit
+    pub fn at(&self, t: f32) -> [u8; 3] {
+        let t = t.rem_euclid(1.);
+        let next = self
+            .stops
+            .iter()
+            .position(|stop| stop.position > t)
+            .unwrap_or(self.stops.len());
+
+        let (from, from_position) = match next {
+            0 => {
+                let last = self.stops.last().unwrap();
+                (last.color, last.position - 1.)
+            }
+            _ => (self.stops[next - 1].color, self.stops[next - 1].position),
+        };
+        let (to, to_position) = match self.stops.get(next) {
+            Some(stop) => (stop.color, stop.position),
+            None => (self.stops[0].color, self.stops[0].position + 1.),
+        };
+
+        let k = if to_position > from_position {
+            (t - from_position) / (to_position - from_position)
+        } else {
+            0.
+        };
+        let mix = |i: usize| (from.0[i] as f32 + (to.0[i] as f32 - from.0[i] as f32) * k) as u8;
+        [mix(0), mix(1), mix(2)]
+    }
+}
+
+// What the palette position of an escaping point follows
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Iterations,
+    // Iteration rank among the pixels on screen, spreads the palette evenly over them
+    Histogram,
+    // Orbit traps, closest distance of the orbit to the origin, the real axis or both axes
+    PointTrap,
+    LineTrap,
+    CrossTrap,
+}
+
+pub const MODES: [Mode; 5] = [
+    Mode::Iterations,
+    Mode::Histogram,
+    Mode::PointTrap,
+    Mode::LineTrap,
+    Mode::CrossTrap,
+];
+
+// What the color of a bounded point follows
+#[derive(Clone, Copy, PartialEq)]
+pub enum InteriorMode {
+    // The interior color of the palette
+    Flat,
+    // |z| at the end of the orbit
+    Magnitude,
+    // Length of the attracting cycle
+    Period,
+}
+
+pub const INTERIOR_MODES: [InteriorMode; 3] = [
+    InteriorMode::Flat,
+    InteriorMode::Magnitude,
+    InteriorMode::Period,
+];
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Iterations => "Iterations",
+            Mode::Histogram => "Histogram",
+            Mode::PointTrap => "Point trap",
+            Mode::LineTrap => "Line trap",
+            Mode::CrossTrap => "Cross trap",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = MODES.iter().position(|&mode| mode == self).unwrap();
+        MODES[(index + 1) % MODES.len()]
+    }
+}
+
+impl InteriorMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            InteriorMode::Flat => "Flat",
+            InteriorMode::Magnitude => "Magnitude",
+            InteriorMode::Period => "Period",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = INTERIOR_MODES
+            .iter()
+            .position(|&mode| mode == self)
+            .unwrap();
+        INTERIOR_MODES[(index + 1) % INTERIOR_MODES.len()]
+    }
+}
+
+// Share of the escaped samples under each iteration count
+pub struct Histogram {
+    cumulative: Vec<f32>,
+}
+
+impl Histogram {
+    pub fn new<'a>(samples: impl Iterator<Item = &'a Sample>) -> Self {
+        let mut counts: Vec<u32> = Vec::new();
+        samples.for_each(|sample| {
+            if let Sample::Escaped(n, ..) = sample {
+                let bin = n.max(0.) as usize;
+                if bin >= counts.len() {
+                    counts.resize(bin + 1, 0);
+                }
+                counts[bin] += 1;
+            }
+        });
+
+        let total = counts.iter().sum::<u32>().max(1) as f32;
+        let mut sum = 0;
+        Self {
+            cumulative: counts
+                .iter()
+                .map(|count| {
+                    sum += count;
+                    sum as f32 / total
+                })
+                .collect(),
+        }
+    }
+
+    // Share of the samples under n, interpolated inside its bin so the bands stay smooth
+    fn rank(&self, n: f32) -> f32 {
+        let bin = n.max(0.) as usize;
+        let below = match bin {
+            0 => 0.,
+            _ => self.cumulative.get(bin - 1).copied().unwrap_or(1.),
+        };
+        let until = self.cumulative.get(bin).copied().unwrap_or(1.);
+        below + (until - below) * n.max(0.).fract()
+    }
+}
+
+// How a pane maps smooth iteration counts to colors
+#[derive(Clone)]
+pub struct Coloring {
+    pub palette: Palette,
+    pub density: f32,
+    pub offset: f32,
+    // Distance estimation shading instead of the flat palette colors
+    pub lighting: bool,
+    pub mode: Mode,
+    pub interior: InteriorMode,
+}
+
+fn normalize([x, y, z]: [f32; 3]) -> [f32; 3] {
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// Lambert diffuse and Blinn specular lighting of the surface as a height map,
+// fading to black on the boundary
+fn light(color: [u8; 3], surface: Surface, pixel_size: f64) -> [u8; 3] {
+    let normal = normalize([surface.normal.re, surface.normal.im, HEIGHT]);
+    let half = normalize([LIGHT[0], LIGHT[1], LIGHT[2] + 1.]);
+    let diffuse = dot(normal, LIGHT).max(0.);
+    let specular = dot(normal, half).max(0.).powf(SHININESS);
+    let boundary = ((surface.distance / pixel_size) as f32 / BOUNDARY_WIDTH).clamp(0., 1.);
+
+    color.map(|channel| {
+        let lit = channel as f32 * (AMBIENT + DIFFUSE * diffuse) + 255. * SPECULAR * specular;
+        (lit * boundary).min(255.) as u8
+    })
+}
+
+impl Coloring {
+    pub fn new(palette: Palette) -> Self {
+        Self {
+            palette,
+            density: 1.,
+            offset: 0.,
+            lighting: false,
+            mode: Mode::Iterations,
+            interior: InteriorMode::Flat,
+        }
+    }
+
+    // Palette, modes and lighting, as shown by the readout
+    pub fn describe(&self) -> String {
+        format!(
+            "{}, {}, {} interior{}",
+            self.palette.name,
+            self.mode.name(),
+            self.interior.name(),
+            if self.lighting { ", lit" } else { "" }
+        )
+    }
+
+    // Color of a sample, the histogram of the image is needed by the histogram mode
+    pub fn color(&self, sample: Sample, histogram: Option<&Histogram>, pixel_size: f64) -> [u8; 4] {
+        let trap = |distance: f32| -distance.max(TRAP_MIN_DISTANCE).ln() / TRAP_PERIOD;
+        let [r, g, b] = match sample {
+            Sample::Escaped(n, surface, traps) => {
+                let position = match (self.mode, histogram) {
+                    (Mode::Histogram, Some(histogram)) => histogram.rank(n),
+                    (Mode::PointTrap, _) => trap(traps.point),
+                    (Mode::LineTrap, _) => trap(traps.line),
+                    (Mode::CrossTrap, _) => trap(traps.cross),
+                    _ => n / PALETTE_PERIOD,
+                };
+                let color = self.palette.at(position * self.density + self.offset);
+                match surface {
+                    Some(surface) if self.lighting => light(color, surface, pixel_size),
+                    _ => color,
+                }
+            }
+            Sample::Inside(Some(Interior { magnitude, .. }))
+                if self.interior == InteriorMode::Magnitude =>
+            {
+                self.palette
+                    .at(magnitude / MAGNITUDE_PERIOD * self.density + self.offset)
+            }
+            Sample::Inside(Some(Interior {
+                period: Some(period),
+                ..
+            })) if self.interior == InteriorMode::Period => {
+                self.palette.at(period as f32 * PERIOD_STEP + self.offset)
+            }
+            Sample::Inside(_) => self.palette.interior.0,
+            // Basin hue, darkened by the number of steps
+            Sample::Converged {
+                basin: Some(basin),
+                steps,
+            } => {
+                let shade = 0.5f32.powf(steps * self.density / SHADE_STEPS);
+                self.palette
+                    .at(basin + self.offset)
+                    .map(|channel| (channel as f32 * shade) as u8)
+            }
+            Sample::Converged { basin: None, steps } => self
+                .palette
+                .at(steps / PALETTE_PERIOD * self.density + self.offset),
+        };
+        [r, g, b, 255]
+    }
+}
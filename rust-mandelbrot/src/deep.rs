@@ -0,0 +1,168 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use dashu_float::{round::mode::HalfAway, DBig, FBig};
+use num_complex::Complex;
+
+use crate::fractal::{escaped, Approach, Interior, Sample, BAILOUT};
+
+pub type Real = FBig<HalfAway, 2>;
+
+// Under this scale f64 can't tell neighbouring pixels apart anymore
+pub const DEEP_SCALE: f64 = 1e-12;
+// Smallest scale before the f64 pixel deltas underflow
+pub const MIN_SCALE: f64 = 1e-300;
+// Largest scale, well past the whole set, zooming out further only leads to overflows
+pub const MAX_SCALE: f64 = 1e3;
+
+// Bits kept on top of the ones needed to reach the pixel size
+const GUARD_BITS: usize = 64;
+
+// Scales down to zero or invalid ones get the bits of the smallest f64, instead of overflowing
+pub fn precision(scale: f64) -> usize {
+    (-scale.max(f64::MIN_POSITIVE).log2()).max(0.) as usize + GUARD_BITS
+}
+
+fn real(x: f64, precision: usize) -> Real {
+    Real::try_from(x).unwrap().with_precision(precision).value()
+}
+
+// Arbitrary precision complex number, used for the view center and the reference orbit
+#[derive(Clone, PartialEq)]
+pub struct BigComplex {
+    pub re: Real,
+    pub im: Real,
+}
+
+impl BigComplex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self {
+            re: real(re, GUARD_BITS),
+            im: real(im, GUARD_BITS),
+        }
+    }
+
+    // Parse decimal coordinates, keeping every digit given
+    pub fn parse(re: &str, im: &str) -> Result<Self, String> {
+        let parse = |x: &str| -> Result<Real, String> {
+            let decimal: DBig = x
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid number {}", x))?;
+            let precision = (decimal.digits() as f64 * 10f64.log2()) as usize + GUARD_BITS;
+            Ok(decimal.with_base_and_precision::<2>(precision).value())
+        };
+        Ok(Self {
+            re: parse(re)?,
+            im: parse(im)?,
+        })
+    }
+
+    pub fn to_f64(&self) -> Complex<f64> {
+        Complex::new(self.re.to_f64().value(), self.im.to_f64().value())
+    }
+
+    // Self moved by a small f64 delta, keeping at least the given precision
+    pub fn translate(&self, delta: Complex<f64>, precision: usize) -> Self {
+        let precision = precision.max(self.re.precision());
+        Self {
+            re: self.re.clone().with_precision(precision).value() + real(delta.re, precision),
+            im: self.im.clone().with_precision(precision).value() + real(delta.im, precision),
+        }
+    }
+
+    // self - other, small enough for f64
+    pub fn difference(&self, other: &BigComplex) -> Complex<f64> {
+        Complex::new(
+            (&self.re - &other.re).to_f64().value(),
+            (&self.im - &other.im).to_f64().value(),
+        )
+    }
+
+    // Decimal form with enough digits for the given scale
+    pub fn to_decimal(&self, scale: f64) -> (String, String) {
+        let digits = (-scale.log10()).max(0.) as usize + 6;
+        let decimal = |x: &Real| -> String {
+            let x: DBig = x.clone().with_base_and_precision::<10>(digits).value();
+            x.to_string()
+        };
+        (decimal(&self.re), decimal(&self.im))
+    }
+}
+
+// Orbit of the view center computed at full precision, every other pixel
+// only iterates its f64 difference to it
+pub struct Reference {
+    orbit: Vec<Complex<f64>>,
+    iterations: u32,
+}
+
+impl Reference {
+    // None when cancelled, deep zooms take up to a second at full precision
+    pub fn new(
+        c: &BigComplex,
+        pixel_size: f64,
+        iterations: u32,
+        cancelled: &AtomicBool,
+    ) -> Option<Self> {
+        let precision = precision(pixel_size);
+        let c = c.translate(Complex::new(0., 0.), precision);
+        let mut z = BigComplex {
+            re: real(0., precision),
+            im: real(0., precision),
+        };
+        let mut orbit = vec![Complex::new(0., 0.)];
+
+        while orbit.len() <= iterations as usize
+            && orbit.last().unwrap().norm_sqr() < BAILOUT * BAILOUT
+        {
+            if cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            z = BigComplex {
+                re: &z.re * &z.re - &z.im * &z.im + &c.re,
+                im: real(2., precision) * &z.re * &z.im + &c.im,
+            };
+            orbit.push(z.to_f64());
+        }
+
+        Some(Self { orbit, iterations })
+    }
+
+    // Same result as fractal::escape_time with z^2 + c for the point reference + dc, without the
+    // periodicity shortcut: once dc is under the f64 resolution of the orbit, every
+    // pixel orbit looks like the reference one
+    pub fn escape_time(&self, dc: Complex<f64>) -> Sample {
+        let mut dz = Complex::new(0., 0.);
+        // Derivative against c of the full pixel orbit
+        let mut derivative = Complex::new(0., 0.);
+        let mut n = 0;
+        let mut z = Complex::new(0., 0.);
+        let mut approach = Approach::default();
+
+        for i in 1..=self.iterations {
+            derivative = 2. * (self.orbit[n] + dz) * derivative + 1.;
+            dz = (2. * self.orbit[n] + dz) * dz + dc;
+            n += 1;
+
+            z = self.orbit[n] + dz;
+            let norm_sqr = z.norm_sqr();
+            approach.update(z, norm_sqr);
+            if norm_sqr >= BAILOUT * BAILOUT {
+                return escaped(i, z, Some(derivative), approach.traps(), 2.);
+            }
+
+            // Glitch correction: when the pixel orbit gets closer to 0 than to the
+            // reference one, or the reference escaped, carry on from the start of
+            // the reference orbit
+            if z.norm_sqr() < dz.norm_sqr() || n == self.orbit.len() - 1 {
+                dz = z;
+                n = 0;
+            }
+        }
+
+        Sample::Inside(Some(Interior {
+            magnitude: z.norm() as f32,
+            period: None,
+        }))
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use num_complex::Complex;
+
+use crate::fractal::Interior;
+
+pub const FORMULAS: [&str; 5] = [
+    "Mandelbrot",
+    "Multibrot",
+    "Burning Ship",
+    "Tricorn",
+    "Celtic",
+];
+pub const MAX_DEGREE: u32 = 8;
+
+// An escape-time iteration z -> f(z, c), the left pane varies c from z = 0 and
+// the right pane is its julia companion, varying z with c fixed
+pub trait Formula: Send + Sync {
+    fn name(&self) -> String;
+
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64>;
+
+    // How |z| grows per step once escaping, used by the smooth iteration count
+    fn degree(&self) -> f64 {
+        2.
+    }
+
+    // Derivative of the step in z, for the distance estimation, None when not holomorphic
+    fn derivative(&self, _z: Complex<f64>) -> Option<Complex<f64>> {
+        None
+    }
+
+    // Attracting cycle of the parameters known to never escape, checked before iterating
+    fn interior(&self, _c: Complex<f64>) -> Option<Interior> {
+        None
+    }
+
+    // Whether deep zooms can use the reference orbit perturbation
+    fn perturbation(&self) -> bool {
+        false
+    }
+
+    // Whether the step is z^2 + c, iterated by the vectorized kernel
+    fn vectorized(&self) -> bool {
+        false
+    }
+}
+
+pub struct Mandelbrot;
+
+impl Formula for Mandelbrot {
+    fn name(&self) -> String {
+        "Mandelbrot".to_string()
+    }
+
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        z * z + c
+    }
+
+    fn derivative(&self, z: Complex<f64>) -> Option<Complex<f64>> {
+        Some(2. * z)
+    }
+
+    // Main cardioid, attracted to the fixed point z^2 + c = z, and period 2 bulb,
+    // attracted to the cycle z^2 + z + c + 1 = 0
+    fn interior(&self, c: Complex<f64>) -> Option<Interior> {
+        let q = (c.re - 0.25).powi(2) + c.im.powi(2);
+        let (z, period) = if q * (q + c.re - 0.25) <= 0.25 * c.im.powi(2) {
+            ((1. - (1. - 4. * c).sqrt()) / 2., 1)
+        } else if (c.re + 1.).powi(2) + c.im.powi(2) <= 1. / 16. {
+            ((-1. + (-3. - 4. * c).sqrt()) / 2., 2)
+        } else {
+            return None;
+        };
+        Some(Interior {
+            magnitude: z.norm() as f32,
+            period: Some(period),
+        })
+    }
+
+    fn perturbation(&self) -> bool {
+        true
+    }
+
+    fn vectorized(&self) -> bool {
+        true
+    }
+}
+
+pub struct Multibrot {
+    pub degree: u32,
+}
+
+impl Formula for Multibrot {
+    fn name(&self) -> String {
+        format!("Multibrot z^{}", self.degree)
+    }
+
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        z.powu(self.degree) + c
+    }
+
+    fn derivative(&self, z: Complex<f64>) -> Option<Complex<f64>> {
+        Some(self.degree as f64 * z.powu(self.degree - 1))
+    }
+
+    fn degree(&self) -> f64 {
+        self.degree as f64
+    }
+}
+
+pub struct BurningShip;
+
+impl Formula for BurningShip {
+    fn name(&self) -> String {
+        "Burning Ship".to_string()
+    }
+
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        let z = Complex::new(z.re.abs(), z.im.abs());
+        z * z + c
+    }
+}
+
+pub struct Tricorn;
+
+impl Formula for Tricorn {
+    fn name(&self) -> String {
+        "Tricorn".to_string()
+    }
+
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        let z = z.conj();
+        z * z + c
+    }
+}
+
+pub struct Celtic;
+
+impl Formula for Celtic {
+    fn name(&self) -> String {
+        "Celtic".to_string()
+    }
+
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        Complex::new((z.re * z.re - z.im * z.im).abs(), 2. * z.re * z.im) + c
+    }
+}
+
+// Formula from one of the FORMULAS names, the degree is only used by Multibrot
+pub fn formula(name: &str, degree: u32) -> Option<Arc<dyn Formula>> {
+    match name.to_lowercase().as_str() {
+        "mandelbrot" => Some(Arc::new(Mandelbrot)),
+        "multibrot" => Some(Arc::new(Multibrot {
+            degree: degree.clamp(2, MAX_DEGREE),
+        })),
+        "burning ship" | "burningship" => Some(Arc::new(BurningShip)),
+        "tricorn" | "mandelbar" => Some(Arc::new(Tricorn)),
+        "celtic" => Some(Arc::new(Celtic)),
+        _ => None,
+    }
+}
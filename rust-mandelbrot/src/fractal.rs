@@ -0,0 +1,375 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use num_complex::Complex;
+
+use crate::deep::{Reference, DEEP_SCALE};
+use crate::formula::Formula;
+use crate::lanes;
+use crate::viewport::Viewport;
+use crate::BASE_SCALE;
+
+pub const ITERATIONS: u32 = 50;
+pub const BAILOUT: f64 = 256.;
+
+// Squared distance under which an orbit is considered back on a previous point,
+// tightened with the pixel size as deep orbits shadow periodic ones for a long time
+const PERIODICITY_EPSILON: f64 = 1e-24;
+const PERIODICITY_PIXEL_FACTOR: f64 = 1e-3;
+
+// Longest cycle looked for at the end of a bounded orbit
+const MAX_PERIOD: usize = 1024;
+// Distance under which the end of an orbit is back on one of its previous points
+const PERIOD_EPSILON: f64 = 1e-6;
+
+// What a pixel computed to, turned into a color by the pane coloring
+#[derive(Clone, Copy)]
+pub enum Sample {
+    // Smooth iteration count of an escaping point, with its surface when the formula has a
+    // derivative and how close its orbit came to the traps
+    Escaped(f32, Option<Surface>, Traps),
+    // Still bounded when the iteration budget ran out, with where its orbit ended if iterated
+    Inside(Option<Interior>),
+    // Newton's method settled, basin locates the root among the polynomial roots
+    Converged { basin: Option<f32>, steps: f32 },
+}
+
+// Distance estimation of an escaping point
+#[derive(Clone, Copy)]
+pub struct Surface {
+    // Estimated distance to the set boundary in plane units, f64 to hold deep zooms,
+    // coloring::light divides it by the pixel size of the view showing it
+    pub distance: f64,
+    // Unit direction away from the set, z / dz
+    pub normal: Complex<f32>,
+}
+
+// Closest approach of an orbit to the origin, to the real axis and to the cross of both axes
+#[derive(Clone, Copy)]
+pub struct Traps {
+    pub point: f32,
+    pub line: f32,
+    pub cross: f32,
+}
+
+// Traps while the orbit is iterated, squared for the origin so the loop takes no square
+// root, the same way as the lanes kernel
+#[derive(Clone, Copy)]
+pub struct Approach {
+    point: f64,
+    line: f64,
+    cross: f64,
+}
+
+impl Default for Approach {
+    fn default() -> Self {
+        Self {
+            point: f64::INFINITY,
+            line: f64::INFINITY,
+            cross: f64::INFINITY,
+        }
+    }
+}
+
+impl Approach {
+    pub fn update(&mut self, z: Complex<f64>, norm_sqr: f64) {
+        let (re, im) = (z.re.abs(), z.im.abs());
+        self.point = self.point.min(norm_sqr);
+        self.line = self.line.min(im);
+        self.cross = self.cross.min(re.min(im));
+    }
+
+    pub fn traps(&self) -> Traps {
+        Traps {
+            point: self.point.sqrt() as f32,
+            line: self.line as f32,
+            cross: self.cross as f32,
+        }
+    }
+}
+
+// End of a bounded orbit
+#[derive(Clone, Copy)]
+pub struct Interior {
+    // |z| of the last point
+    pub magnitude: f32,
+    // Length of the attracting cycle, None when the orbit didn't settle within the budget
+    pub period: Option<u32>,
+}
+
+// Sample of a point escaping at step i, dz being the derivative of z
+pub fn escaped(
+    i: u32,
+    z: Complex<f64>,
+    dz: Option<Complex<f64>>,
+    traps: Traps,
+    degree: f64,
+) -> Sample {
+    let surface = dz
+        .filter(|dz| dz.is_finite() && *dz != Complex::new(0., 0.))
+        .map(|dz| {
+            let normal = z / dz;
+            Surface {
+                distance: z.norm() * z.norm().ln() / dz.norm(),
+                normal: Complex::new(normal.re as f32, normal.im as f32) / normal.norm() as f32,
+            }
+        });
+    Sample::Escaped(smooth(i, z, degree) as f32, surface, traps)
+}
+
+type PointSampler = Box<dyn Fn(f64, f64) -> Sample + Send + Sync>;
+type PointsSampler = Box<dyn Fn(&[(f64, f64)]) -> Vec<Sample> + Send + Sync>;
+
+// Samples at pixel positions, sub-pixel positions are used for antialiasing
+pub struct Sampler {
+    point: PointSampler,
+    // Many points at once, when the fractal has a vectorized kernel
+    points: Option<PointsSampler>,
+}
+
+impl Sampler {
+    pub fn new(point: impl Fn(f64, f64) -> Sample + Send + Sync + 'static) -> Self {
+        Self {
+            point: Box::new(point),
+            points: None,
+        }
+    }
+
+    pub fn with_lanes(
+        mut self,
+        points: impl Fn(&[(f64, f64)]) -> Vec<Sample> + Send + Sync + 'static,
+    ) -> Self {
+        self.points = Some(Box::new(points));
+        self
+    }
+
+    pub fn sample(&self, x: f64, y: f64) -> Sample {
+        (self.point)(x, y)
+    }
+
+    // Same as sampling each point, through the vectorized kernel if there is one
+    pub fn samples(&self, points: &[(f64, f64)]) -> Vec<Sample> {
+        match &self.points {
+            Some(sampler) => sampler(points),
+            None => self.scalar_samples(points),
+        }
+    }
+
+    // One point at a time, the reference for the vectorized kernels
+    pub fn scalar_samples(&self, points: &[(f64, f64)]) -> Vec<Sample> {
+        points.iter().map(|&(x, y)| self.sample(x, y)).collect()
+    }
+}
+
+// Iteration budget growing with the zoom depth, so the boundary stays detailed
+pub fn auto_iterations(scale: f64) -> u32 {
+    let zoom = (BASE_SCALE / scale).log10().max(0.);
+    (ITERATIONS as f64 * (1. + zoom).powf(1.5)) as u32
+}
+
+// Brent's cycle detection, the orbit is compared to a point saved at doubling intervals
+pub struct Periodicity {
+    saved: Complex<f64>,
+    epsilon: f64,
+    interval: u32,
+    steps: u32,
+}
+
+pub fn periodicity_epsilon(pixel_size: f64) -> f64 {
+    PERIODICITY_EPSILON.min((pixel_size * PERIODICITY_PIXEL_FACTOR).powi(2))
+}
+
+impl Periodicity {
+    pub fn new(z: Complex<f64>, pixel_size: f64) -> Self {
+        Self {
+            saved: z,
+            epsilon: periodicity_epsilon(pixel_size),
+            interval: 1,
+            steps: 0,
+        }
+    }
+
+    // Period of the cycle when the orbit came back on the saved point, so it will never escape
+    pub fn check(&mut self, z: Complex<f64>) -> Option<u32> {
+        self.steps += 1;
+        if (z - self.saved).norm_sqr() < self.epsilon {
+            return Some(self.steps);
+        }
+
+        if self.steps == self.interval {
+            self.saved = z;
+            self.steps = 0;
+            self.interval *= 2;
+        }
+        None
+    }
+}
+
+// Iterate from z until escaping, the derivative is taken against c in the parameter
+// plane and against the starting z in the julia one
+pub fn escape_time(
+    formula: &dyn Formula,
+    mut z: Complex<f64>,
+    c: Complex<f64>,
+    iterations: u32,
+    pixel_size: f64,
+    parameter: bool,
+) -> Sample {
+    let mut periodicity = Periodicity::new(z, pixel_size);
+    let mut approach = Approach::default();
+    let (mut dz, dc) = if parameter {
+        (Some(Complex::new(0., 0.)), Complex::new(1., 0.))
+    } else {
+        (Some(Complex::new(1., 0.)), Complex::new(0., 0.))
+    };
+
+    for i in 1..=iterations {
+        dz = dz.and_then(|dz| Some(formula.derivative(z)? * dz + dc));
+        z = formula.step(z, c);
+        let norm_sqr = z.norm_sqr();
+        approach.update(z, norm_sqr);
+        if norm_sqr >= BAILOUT * BAILOUT {
+            return escaped(i, z, dz, approach.traps(), formula.degree());
+        }
+        if let Some(period) = periodicity.check(z) {
+            return Sample::Inside(Some(Interior {
+                magnitude: z.norm() as f32,
+                period: Some(period),
+            }));
+        }
+    }
+
+    Sample::Inside(Some(Interior {
+        magnitude: z.norm() as f32,
+        period: None,
+    }))
+}
+
+// Orbit of a single point, for display
+pub struct Orbit {
+    pub points: Vec<Complex<f64>>,
+    // Step at which the point escaped, None when still bounded
+    pub escaped: Option<u32>,
+    // Length of the cycle a bounded orbit settled on, None when it didn't within the budget
+    pub period: Option<usize>,
+}
+
+pub fn orbit(
+    formula: &dyn Formula,
+    mut z: Complex<f64>,
+    c: Complex<f64>,
+    iterations: u32,
+) -> Orbit {
+    let mut points = vec![z];
+    for i in 1..=iterations {
+        z = formula.step(z, c);
+        points.push(z);
+        if z.norm_sqr() >= BAILOUT * BAILOUT {
+            return Orbit {
+                points,
+                escaped: Some(i),
+                period: None,
+            };
+        }
+    }
+
+    let last = points.len() - 1;
+    let period = (1..=MAX_PERIOD.min(last))
+        .find(|&period| (points[last - period] - points[last]).norm() < PERIOD_EPSILON);
+    Orbit {
+        points,
+        escaped: None,
+        period,
+    }
+}
+
+fn smooth(i: u32, z: Complex<f64>, degree: f64) -> f64 {
+    i as f64 + 1. - z.norm().ln().ln() / degree.ln()
+}
+
+// Parameter plane of the formula, the mandelbrot set for z^2 + c,
+// none when cancelled while computing the reference orbit of a deep zoom
+pub fn mandelbrot(
+    viewport: &Viewport,
+    formula: &Arc<dyn Formula>,
+    iterations: u32,
+    cancelled: &AtomicBool,
+) -> Option<Sampler> {
+    let viewport = viewport.clone();
+    let formula = formula.clone();
+
+    if viewport.scale < DEEP_SCALE && formula.perturbation() {
+        // Perturbation around the view center, the interior shortcut is left out as
+        // f64 can't place the pixels against the cardioid at this depth
+        let reference = Reference::new(
+            &viewport.center,
+            viewport.pixel_size(),
+            iterations,
+            cancelled,
+        )?;
+        return Some(Sampler::new(move |x, y| {
+            reference.escape_time(viewport.delta(x, y))
+        }));
+    }
+
+    let sampler = {
+        let (viewport, formula) = (viewport.clone(), formula.clone());
+        Sampler::new(move |x, y| {
+            let c = viewport.point_f64(x, y);
+            if let Some(interior) = formula.interior(c) {
+                Sample::Inside(Some(interior))
+            } else {
+                escape_time(
+                    formula.as_ref(),
+                    Complex::new(0., 0.),
+                    c,
+                    iterations,
+                    viewport.pixel_size(),
+                    true,
+                )
+            }
+        })
+    };
+    if !formula.vectorized() {
+        return Some(sampler);
+    }
+    Some(sampler.with_lanes(move |points| {
+        let start = |x, y| {
+            let c = viewport.point_f64(x, y);
+            match formula.interior(c) {
+                Some(interior) => Err(Sample::Inside(Some(interior))),
+                None => Ok((Complex::new(0., 0.), c)),
+            }
+        };
+        lanes::escape_times(points, start, iterations, viewport.pixel_size(), true)
+    }))
+}
+
+pub fn julia(
+    viewport: &Viewport,
+    formula: &Arc<dyn Formula>,
+    c: Complex<f64>,
+    iterations: u32,
+) -> Sampler {
+    let sampler = {
+        let (viewport, formula) = (viewport.clone(), formula.clone());
+        Sampler::new(move |x, y| {
+            escape_time(
+                formula.as_ref(),
+                viewport.point_f64(x, y),
+                c,
+                iterations,
+                viewport.pixel_size(),
+                false,
+            )
+        })
+    };
+    if !formula.vectorized() {
+        return sampler;
+    }
+    let viewport = viewport.clone();
+    sampler.with_lanes(move |points| {
+        let start = |x, y| Ok((viewport.point_f64(x, y), c));
+        lanes::escape_times(points, start, iterations, viewport.pixel_size(), false)
+    })
+}
@@ -0,0 +1,400 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use num_complex::Complex;
+use rayon::prelude::*;
+
+use crate::bookmarks::{Bookmark, BOOKMARKS_FILE};
+use crate::coloring::{Coloring, Histogram, Mode, Palette, INTERIOR_MODES, MODES, PALETTES_FILE};
+use crate::deep::BigComplex;
+use crate::formula::{formula, FORMULAS};
+use crate::fractal::{self, auto_iterations, Sampler};
+use crate::newton::{self, Polynomial};
+use crate::viewport::{check_scale, Viewport};
+use crate::{BASE_CENTER, BASE_SCALE};
+
+// Rows computed at once before being streamed to the png encoder
+const BAND_HEIGHT: u32 = 64;
+// Pixel spacing of the samples making up the histogram of the histogram coloring
+const HISTOGRAM_STEP: u32 = 4;
+// Samples per side of a pixel, beyond it the image takes hours for no visible change
+const MAX_ANTIALIASING: u32 = 16;
+
+pub const USAGE: &str = "Usage: rust-mandelbrot render [options] -o out.png
+       rust-mandelbrot animate --keyframes zoom.toml [options] -o out.gif|directory
+       rust-mandelbrot terminal [options]
+
+Options:
+    --location share    View copied from the explorer, the following options override it
+    --bookmark name     View saved in the bookmarks file
+    --center x,y        Center of the image, any number of digits
+    --scale s           Span of the shortest side of the image
+    --size WxH          Size of the image in pixels
+    --iterations N      Maximum number of iterations, follows the scale by default
+    --julia re,im       Render the julia set of c = re + im i
+    --formula name      Mandelbrot, Multibrot, Burning Ship, Tricorn or Celtic
+    --degree d          Power of the Multibrot formula
+    --newton p          Newton fractal of the polynomial p, 'z^3 - 1' or 'roots: 1, i, -i',
+                        with --julia giving the nova parameter
+    --relaxation r      Newton step factor
+    --aa N              Antialiasing, N x N samples per pixel, up to 16
+    --palette name      Palette name
+    --density d         Palette density
+    --offset o          Palette offset
+    --lighting on|off   Distance estimation lighting
+    --coloring mode     Iterations, Histogram, Point-trap, Line-trap or Cross-trap
+    --interior mode     Flat, Magnitude or Period
+    --keyframes file    Keyframes of the animation
+    --fps n             Frames per second of the animation
+    -o, --output file   Output png file, or gif file or directory of numbered pngs for animations
+
+Terminal keys: arrows to move, + and - to zoom, PageUp, PageDown and Home for the iterations,
+q or Escape to quit";
+
+pub struct Options {
+    pub center: Option<BigComplex>,
+    pub scale: f64,
+    pub size: (u32, u32),
+    pub iterations: Option<u32>,
+    pub julia: Option<Complex<f64>>,
+    pub formula: String,
+    pub degree: u32,
+    pub newton: Option<String>,
+    pub relaxation: f64,
+    pub antialiasing: u32,
+    pub palette: String,
+    pub density: f32,
+    pub offset: f32,
+    pub lighting: bool,
+    pub coloring: String,
+    pub interior: String,
+    pub keyframes: Option<String>,
+    pub fps: f64,
+    pub output: Option<String>,
+}
+
+fn split_pair(value: &str, separator: char) -> Result<(&str, &str), String> {
+    value
+        .split_once(separator)
+        .ok_or_else(|| format!("Expected a pair separated by '{}': {}", separator, value))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid number {}", value))
+}
+
+fn apply_bookmark(options: &mut Options, bookmark: &Bookmark) -> Result<(), String> {
+    bookmark.check()?;
+    options.center = Some(bookmark.center()?);
+    options.scale = bookmark.scale;
+    options.iterations = bookmark.iterations;
+    options.formula = bookmark.formula.clone();
+    options.degree = bookmark.degree;
+    options.palette = bookmark.palette.clone();
+    options.julia = bookmark.julia();
+    Ok(())
+}
+
+pub fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        center: None,
+        scale: BASE_SCALE,
+        size: (1920, 1080),
+        iterations: None,
+        julia: None,
+        formula: FORMULAS[0].to_string(),
+        degree: 3,
+        newton: None,
+        relaxation: 1.,
+        antialiasing: 1,
+        palette: Palette::builtins()[0].name.clone(),
+        density: 1.,
+        offset: 0.,
+        lighting: false,
+        coloring: MODES[0].name().to_string(),
+        interior: INTERIOR_MODES[0].name().to_string(),
+        keyframes: None,
+        fps: 25.,
+        output: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Err(USAGE.to_string());
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}\n\n{}", arg, USAGE))?;
+        match arg.as_str() {
+            "--location" => apply_bookmark(&mut options, &Bookmark::from_share(value)?)?,
+            "--bookmark" => {
+                let bookmark = Bookmark::load_all(BOOKMARKS_FILE)
+                    .into_iter()
+                    .find(|bookmark| bookmark.name.eq_ignore_ascii_case(value))
+                    .ok_or_else(|| format!("Unknown bookmark {}", value))?;
+                apply_bookmark(&mut options, &bookmark)?;
+            }
+            "--center" => {
+                let (re, im) = split_pair(value, ',')?;
+                options.center = Some(BigComplex::parse(re, im)?);
+            }
+            "--scale" => options.scale = check_scale(parse_number(value)?)?,
+            "--size" => {
+                let (width, height) = split_pair(value, 'x')?;
+                options.size = (parse_number(width)?, parse_number(height)?);
+            }
+            "--iterations" => options.iterations = Some(parse_number(value)?),
+            "--julia" => {
+                let (re, im) = split_pair(value, ',')?;
+                options.julia = Some(Complex::new(parse_number(re)?, parse_number(im)?));
+            }
+            "--formula" => options.formula = value.clone(),
+            "--degree" => options.degree = parse_number(value)?,
+            "--newton" => options.newton = Some(value.clone()),
+            "--relaxation" => options.relaxation = parse_number(value)?,
+            "--aa" => {
+                options.antialiasing = match parse_number::<u32>(value)? {
+                    aa @ 1..=MAX_ANTIALIASING => aa,
+                    _ => {
+                        return Err(format!(
+                            "Antialiasing must be between 1 and {}: {}",
+                            MAX_ANTIALIASING, value
+                        ))
+                    }
+                }
+            }
+            "--palette" => options.palette = value.clone(),
+            "--density" => options.density = parse_number(value)?,
+            "--offset" => options.offset = parse_number(value)?,
+            "--lighting" => {
+                options.lighting = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("Expected on or off for --lighting: {}", value)),
+                }
+            }
+            "--coloring" => options.coloring = value.clone(),
+            "--interior" => options.interior = value.clone(),
+            "--keyframes" => options.keyframes = Some(value.clone()),
+            "--fps" => options.fps = parse_number(value)?,
+            "-o" | "--output" => options.output = Some(value.clone()),
+            _ => return Err(format!("Unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    Ok(options)
+}
+
+pub fn coloring(options: &Options) -> Result<Coloring, String> {
+    let mut coloring = Palette::load_all(PALETTES_FILE)
+        .into_iter()
+        .find(|palette| palette.name.eq_ignore_ascii_case(&options.palette))
+        .map(Coloring::new)
+        .ok_or_else(|| format!("Unknown palette {}", options.palette))?;
+    coloring.density = options.density;
+    coloring.offset = options.offset;
+    coloring.lighting = options.lighting;
+
+    // Names are also accepted with dashes instead of spaces
+    let matches = |name: &str, value: &str| name.eq_ignore_ascii_case(&value.replace('-', " "));
+    coloring.mode = MODES
+        .into_iter()
+        .find(|mode| matches(mode.name(), &options.coloring))
+        .ok_or_else(|| format!("Unknown coloring {}", options.coloring))?;
+    coloring.interior = INTERIOR_MODES
+        .into_iter()
+        .find(|mode| matches(mode.name(), &options.interior))
+        .ok_or_else(|| format!("Unknown interior coloring {}", options.interior))?;
+    Ok(coloring)
+}
+
+// Histogram of the image for the histogram coloring, from a coarse grid of samples
+fn histogram(viewport: &Viewport, sampler: &Sampler, coloring: &Coloring) -> Option<Histogram> {
+    if coloring.mode != Mode::Histogram {
+        return None;
+    }
+
+    let samples: Vec<_> = (0..viewport.height)
+        .into_par_iter()
+        .step_by(HISTOGRAM_STEP as usize)
+        .flat_map_iter(|y| {
+            let points: Vec<(f64, f64)> = (0..viewport.width)
+                .step_by(HISTOGRAM_STEP as usize)
+                .map(|x| (x as f64, y as f64))
+                .collect();
+            sampler.samples(&points)
+        })
+        .collect();
+    Some(Histogram::new(samples.iter()))
+}
+
+// Fractal of the options over the viewport, julia is the julia or nova parameter if any
+pub fn sampler(
+    options: &Options,
+    viewport: &Viewport,
+    julia: Option<Complex<f64>>,
+) -> Result<Sampler, String> {
+    let iterations = options
+        .iterations
+        .unwrap_or_else(|| auto_iterations(viewport.scale));
+
+    Ok(match &options.newton {
+        Some(text) => newton::newton(
+            viewport,
+            &Arc::new(Polynomial::parse(text)?),
+            options.relaxation,
+            julia.unwrap_or_default(),
+            iterations,
+        ),
+        None => {
+            let formula = formula(&options.formula, options.degree)
+                .ok_or_else(|| format!("Unknown formula {}", options.formula))?;
+            match julia {
+                Some(c) => fractal::julia(viewport, &formula, c, iterations),
+                // Nothing cancels a headless render
+                None => {
+                    fractal::mandelbrot(viewport, &formula, iterations, &AtomicBool::new(false))
+                        .ok_or_else(|| "Render cancelled".to_string())?
+                }
+            }
+        }
+    })
+}
+
+// RGB colors of a row, each pixel averaging antialiasing x antialiasing samples
+fn render_row(
+    sampler: &Sampler,
+    coloring: &Coloring,
+    histogram: Option<&Histogram>,
+    viewport: &Viewport,
+    y: u32,
+    antialiasing: u32,
+) -> Vec<u8> {
+    let samples = antialiasing * antialiasing;
+    let points: Vec<(f64, f64)> = (0..viewport.width)
+        .flat_map(|x| {
+            (0..samples).map(move |i| {
+                (
+                    x as f64 + (i % antialiasing) as f64 / antialiasing as f64,
+                    y as f64 + (i / antialiasing) as f64 / antialiasing as f64,
+                )
+            })
+        })
+        .collect();
+
+    sampler
+        .samples(&points)
+        .chunks(samples as usize)
+        .flat_map(|pixel| {
+            let mut sum = [0; 3];
+            pixel.iter().for_each(|&sample| {
+                let color = coloring.color(sample, histogram, viewport.pixel_size());
+                (0..3).for_each(|channel| sum[channel] += color[channel] as u32);
+            });
+            sum.map(|channel| (channel / samples) as u8)
+        })
+        .collect()
+}
+
+// RGB colors of the whole image
+pub fn render_image(
+    viewport: &Viewport,
+    sampler: &Sampler,
+    coloring: &Coloring,
+    antialiasing: u32,
+) -> Vec<u8> {
+    let histogram = histogram(viewport, sampler, coloring);
+    (0..viewport.height)
+        .into_par_iter()
+        .flat_map_iter(|y| {
+            render_row(
+                sampler,
+                coloring,
+                histogram.as_ref(),
+                viewport,
+                y,
+                antialiasing,
+            )
+        })
+        .collect()
+}
+
+// Render straight to a png file, band by band so the whole image never has to fit in memory
+pub fn save_png(
+    path: &str,
+    viewport: &Viewport,
+    sampler: &Sampler,
+    coloring: &Coloring,
+    antialiasing: u32,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("Couldn't create {}: {}", path, err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), viewport.width, viewport.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    let mut stream = writer.stream_writer().map_err(|err| err.to_string())?;
+    let histogram = histogram(viewport, sampler, coloring);
+
+    for band in (0..viewport.height).step_by(BAND_HEIGHT as usize) {
+        let rows: Vec<Vec<u8>> = (band..(band + BAND_HEIGHT).min(viewport.height))
+            .into_par_iter()
+            .map(|y| {
+                render_row(
+                    sampler,
+                    coloring,
+                    histogram.as_ref(),
+                    viewport,
+                    y,
+                    antialiasing,
+                )
+            })
+            .collect();
+        rows.iter()
+            .try_for_each(|row| stream.write_all(row))
+            .map_err(|err| err.to_string())?;
+    }
+
+    stream.finish().map_err(|err| err.to_string())
+}
+
+// View of the options, centered on the origin for julia and Newton fractals by default
+pub fn viewport(options: &Options, width: u32, height: u32) -> Viewport {
+    let center = options
+        .center
+        .clone()
+        .unwrap_or_else(|| match (&options.newton, options.julia) {
+            (None, None) => BigComplex::new(BASE_CENTER.re, BASE_CENTER.im),
+            _ => BigComplex::new(0., 0.),
+        });
+    Viewport {
+        center,
+        scale: options.scale,
+        width,
+        height,
+    }
+}
+
+pub fn render(args: &[String]) -> Result<(), String> {
+    let options = parse_options(args)?;
+    let output = options
+        .output
+        .clone()
+        .ok_or_else(|| format!("Missing output file\n\n{}", USAGE))?;
+    let viewport = viewport(&options, options.size.0, options.size.1);
+
+    save_png(
+        &output,
+        &viewport,
+        &sampler(&options, &viewport, options.julia)?,
+        &coloring(&options)?,
+        options.antialiasing,
+    )
+}
@@ -0,0 +1,135 @@
+use std::f64::consts::TAU;
+
+use num_complex::Complex;
+
+// Seconds for a turn around the cardioid at speed 1
+const CIRCLE_PERIOD: f64 = 20.;
+// Radius of the circle in the multiplier plane, the cardioid itself is at 1
+const CIRCLE_RADIUS: f64 = 1.05;
+
+// Points of a path with their time from the start of the recording
+type Path = Vec<(f64, Complex<f64>)>;
+
+// Where the julia pane takes its parameter from
+pub enum Parameter {
+    // The point under the mouse
+    Mouse,
+    Pinned(Complex<f64>),
+    // Played back in a loop
+    Path(Path),
+    // Around the main cardioid
+    Circle,
+}
+
+pub struct Julia {
+    pub parameter: Parameter,
+    pub speed: f64,
+    // Playback time of the path or the circle
+    time: f64,
+    // Start time and points of the path being recorded
+    recording: Option<(f64, Path)>,
+}
+
+// Point of the circle of the multiplier plane mapped by the main cardioid
+fn cardioid_circle(angle: f64) -> Complex<f64> {
+    let multiplier = Complex::from_polar(CIRCLE_RADIUS, angle);
+    multiplier / 2. - multiplier * multiplier / 4.
+}
+
+fn path_at(path: &[(f64, Complex<f64>)], time: f64) -> Complex<f64> {
+    let duration = path[path.len() - 1].0;
+    if duration <= 0. {
+        return path[0].1;
+    }
+
+    let time = time.rem_euclid(duration);
+    let next = path
+        .iter()
+        .position(|&(t, _)| t > time)
+        .unwrap_or(path.len() - 1)
+        .max(1);
+    let ((t0, c0), (t1, c1)) = (path[next - 1], path[next]);
+    if t1 > t0 {
+        c0 + (c1 - c0) * ((time - t0) / (t1 - t0))
+    } else {
+        c1
+    }
+}
+
+impl Julia {
+    pub fn new() -> Self {
+        Self {
+            parameter: Parameter::Mouse,
+            speed: 1.,
+            time: 0.,
+            recording: None,
+        }
+    }
+
+    // Parameter for this frame, mouse is the point under the mouse
+    pub fn c(&self, mouse: Complex<f64>) -> Complex<f64> {
+        match &self.parameter {
+            Parameter::Mouse => mouse,
+            Parameter::Pinned(c) => *c,
+            Parameter::Path(path) => path_at(path, self.time),
+            Parameter::Circle => cardioid_circle(self.time * TAU / CIRCLE_PERIOD),
+        }
+    }
+
+    pub fn advance(&mut self, seconds: f64) {
+        self.time += seconds * self.speed;
+    }
+
+    pub fn pin(&mut self, c: Option<Complex<f64>>) {
+        self.parameter = match c {
+            Some(c) => Parameter::Pinned(c),
+            None => Parameter::Mouse,
+        };
+    }
+
+    pub fn toggle_circle(&mut self) {
+        self.parameter = match self.parameter {
+            Parameter::Circle => Parameter::Mouse,
+            _ => Parameter::Circle,
+        };
+        self.time = 0.;
+    }
+
+    pub fn recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn start_recording(&mut self, c: Complex<f64>, time: f64) {
+        self.recording = Some((time, vec![(0., c)]));
+    }
+
+    pub fn record(&mut self, c: Complex<f64>, time: f64) {
+        if let Some((start, path)) = &mut self.recording {
+            if path.last().map(|&(_, last)| last) != Some(c) {
+                path.push((time - *start, c));
+            }
+        }
+    }
+
+    // A single point pins it, a longer path starts playing
+    pub fn finish_recording(&mut self) {
+        if let Some((_, path)) = self.recording.take() {
+            self.parameter = match path.len() {
+                1 => Parameter::Pinned(path[0].1),
+                _ => Parameter::Path(path),
+            };
+            self.time = 0.;
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.parameter {
+            Parameter::Mouse => "mouse".to_string(),
+            Parameter::Pinned(_) => "pinned".to_string(),
+            Parameter::Path(path) => {
+                format!("path of {} points, speed {:.2}", path.len(), self.speed)
+            }
+            Parameter::Circle => format!("cardioid circle, speed {:.2}", self.speed),
+        }
+    }
+}
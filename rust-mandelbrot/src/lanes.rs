@@ -0,0 +1,253 @@
+use num_complex::Complex;
+use wide::{f64x4, CmpGe, CmpLt};
+
+use crate::fractal::{escaped, periodicity_epsilon, Interior, Sample, Traps, BAILOUT};
+
+// Points iterated together, one per lane of the vectors
+pub const LANES: usize = 4;
+
+// Complex numbers of all the lanes
+#[derive(Clone, Copy)]
+struct Lanes {
+    re: f64x4,
+    im: f64x4,
+}
+
+impl Lanes {
+    fn new(values: [Complex<f64>; LANES]) -> Self {
+        Self {
+            re: f64x4::new(values.map(|value| value.re)),
+            im: f64x4::new(values.map(|value| value.im)),
+        }
+    }
+
+    fn splat(value: Complex<f64>) -> Self {
+        Self {
+            re: f64x4::splat(value.re),
+            im: f64x4::splat(value.im),
+        }
+    }
+
+    fn norm_sqr(self) -> f64x4 {
+        self.re * self.re + self.im * self.im
+    }
+
+    fn get(self, lane: usize) -> Complex<f64> {
+        Complex::new(self.re.to_array()[lane], self.im.to_array()[lane])
+    }
+}
+
+// Same samples as fractal::escape_time with z^2 + c, for LANES points at once: the group
+// iterates until every lane escaped or settled, the lanes in done are left out
+pub fn escape_time(
+    z: [Complex<f64>; LANES],
+    c: [Complex<f64>; LANES],
+    mut done: [Option<Sample>; LANES],
+    iterations: u32,
+    pixel_size: f64,
+    parameter: bool,
+) -> [Sample; LANES] {
+    let (mut z, c) = (Lanes::new(z), Lanes::new(c));
+    let (mut dz, dc) = if parameter {
+        (Lanes::splat(Complex::new(0., 0.)), f64x4::splat(1.))
+    } else {
+        (Lanes::splat(Complex::new(1., 0.)), f64x4::splat(0.))
+    };
+    let (two, bailout) = (f64x4::splat(2.), f64x4::splat(BAILOUT * BAILOUT));
+
+    // Closest squared distance to the origin, distances to the real axis and to both axes
+    let mut point = f64x4::splat(f64::INFINITY);
+    let mut line = point;
+    let mut cross = point;
+
+    // Brent's cycle detection as in fractal::Periodicity, the steps are shared by all lanes
+    let epsilon = f64x4::splat(periodicity_epsilon(pixel_size));
+    let mut saved = z;
+    let (mut interval, mut steps) = (1, 0);
+
+    let mut remaining = done.iter().filter(|sample| sample.is_none()).count();
+    for i in 1..=iterations {
+        if remaining == 0 {
+            break;
+        }
+
+        dz = Lanes {
+            re: two * (z.re * dz.re - z.im * dz.im) + dc,
+            im: two * (z.re * dz.im + z.im * dz.re),
+        };
+        z = Lanes {
+            re: z.re * z.re - z.im * z.im + c.re,
+            im: two * z.re * z.im + c.im,
+        };
+
+        let norm_sqr = z.norm_sqr();
+        let (re, im) = (z.re.abs(), z.im.abs());
+        point = point.min(norm_sqr);
+        line = line.min(im);
+        cross = cross.min(re.min(im));
+
+        steps += 1;
+        let escaping = norm_sqr.cmp_ge(bailout).move_mask();
+        let periodic = Lanes {
+            re: z.re - saved.re,
+            im: z.im - saved.im,
+        }
+        .norm_sqr()
+        .cmp_lt(epsilon)
+        .move_mask();
+
+        // Samples are only built for the lanes finishing at this step
+        if escaping | periodic != 0 {
+            for (lane, sample) in done.iter_mut().enumerate() {
+                if sample.is_some() {
+                    continue;
+                }
+                if escaping & (1 << lane) != 0 {
+                    let traps = Traps {
+                        point: point.to_array()[lane].sqrt() as f32,
+                        line: line.to_array()[lane] as f32,
+                        cross: cross.to_array()[lane] as f32,
+                    };
+                    *sample = Some(escaped(i, z.get(lane), Some(dz.get(lane)), traps, 2.));
+                } else if periodic & (1 << lane) != 0 {
+                    *sample = Some(Sample::Inside(Some(Interior {
+                        magnitude: z.get(lane).norm() as f32,
+                        period: Some(steps),
+                    })));
+                } else {
+                    continue;
+                }
+                remaining -= 1;
+            }
+        }
+
+        if steps == interval {
+            saved = z;
+            steps = 0;
+            interval *= 2;
+        }
+    }
+
+    std::array::from_fn(|lane| {
+        done[lane].unwrap_or_else(|| {
+            Sample::Inside(Some(Interior {
+                magnitude: z.get(lane).norm() as f32,
+                period: None,
+            }))
+        })
+    })
+}
+
+// Samples of any number of points, LANES at a time, start gives the starting z and c
+// of a point, or its sample when it needs no iteration
+pub fn escape_times(
+    points: &[(f64, f64)],
+    start: impl Fn(f64, f64) -> Result<(Complex<f64>, Complex<f64>), Sample>,
+    iterations: u32,
+    pixel_size: f64,
+    parameter: bool,
+) -> Vec<Sample> {
+    points
+        .chunks(LANES)
+        .flat_map(|group| {
+            let mut z = [Complex::new(0., 0.); LANES];
+            let mut c = [Complex::new(0., 0.); LANES];
+            let mut done = [None; LANES];
+            group
+                .iter()
+                .enumerate()
+                .for_each(|(lane, &(x, y))| match start(x, y) {
+                    Ok((start_z, start_c)) => (z[lane], c[lane]) = (start_z, start_c),
+                    Err(sample) => done[lane] = Some(sample),
+                });
+            // Lanes past the end of the last group
+            done[group.len()..].fill(Some(Sample::Inside(None)));
+
+            let samples = escape_time(z, c, done, iterations, pixel_size, parameter);
+            samples.into_iter().take(group.len())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use num_complex::Complex;
+
+    use crate::formula::formula;
+    use crate::fractal::{self, Sample, Sampler};
+    use crate::viewport::Viewport;
+
+    // Bits of everything a sample holds, the kernels have to agree exactly
+    fn key(sample: Sample) -> Vec<u64> {
+        match sample {
+            Sample::Escaped(smooth, surface, traps) => {
+                let mut key = vec![0, smooth.to_bits() as u64];
+                if let Some(surface) = surface {
+                    key.extend([
+                        surface.distance.to_bits(),
+                        surface.normal.re.to_bits() as u64,
+                        surface.normal.im.to_bits() as u64,
+                    ]);
+                }
+                key.extend([traps.point, traps.line, traps.cross].map(|t| t.to_bits() as u64));
+                key
+            }
+            Sample::Inside(interior) => match interior {
+                Some(interior) => vec![
+                    1,
+                    interior.magnitude.to_bits() as u64,
+                    interior.period.map_or(u64::MAX, u64::from),
+                ],
+                None => vec![2],
+            },
+            Sample::Converged { basin, steps } => vec![
+                3,
+                basin.map_or(u64::MAX, |basin| basin.to_bits() as u64),
+                steps.to_bits() as u64,
+            ],
+        }
+    }
+
+    fn assert_same(sampler: &Sampler, viewport: &Viewport) {
+        (0..viewport.height).for_each(|y| {
+            let points: Vec<(f64, f64)> =
+                (0..viewport.width).map(|x| (x as f64, y as f64)).collect();
+            let lanes = sampler.samples(&points);
+            let scalar = sampler.scalar_samples(&points);
+            assert_eq!(lanes.len(), scalar.len());
+            lanes
+                .into_iter()
+                .zip(scalar)
+                .enumerate()
+                .for_each(|(x, (a, b))| {
+                    assert_eq!(key(a), key(b), "pixel ({}, {})", x, y);
+                });
+        });
+    }
+
+    fn viewport(re: f64, im: f64, scale: f64) -> Viewport {
+        let mut viewport = Viewport::new(Complex::new(re, im), scale);
+        // Odd width so the last group of each row is partly filled
+        viewport.resize(161, 120);
+        viewport
+    }
+
+    #[test]
+    fn mandelbrot_lanes_match_scalar() {
+        let formula = formula("Mandelbrot", 2).unwrap();
+        let viewport = viewport(-0.7436, 0.1318, 0.01);
+        let sampler =
+            fractal::mandelbrot(&viewport, &formula, 500, &AtomicBool::new(false)).unwrap();
+        assert_same(&sampler, &viewport);
+    }
+
+    #[test]
+    fn julia_lanes_match_scalar() {
+        let formula = formula("Mandelbrot", 2).unwrap();
+        let viewport = viewport(0., 0., 3.);
+        let sampler = fractal::julia(&viewport, &formula, Complex::new(-0.123, 0.745), 500);
+        assert_same(&sampler, &viewport);
+    }
+}
@@ -0,0 +1,10 @@
+use num_complex::Complex;
+
+pub mod deep;
+pub mod formula;
+pub mod fractal;
+pub mod lanes;
+pub mod viewport;
+
+pub const BASE_SCALE: f64 = 4.;
+pub const BASE_CENTER: Complex<f64> = Complex::new(-0.5, 0.);
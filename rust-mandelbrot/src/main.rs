@@ -1,176 +1,722 @@
+use std::sync::Arc;
+
 use macroquad::prelude::*;
 use num_complex::Complex;
-use palette::{Hsv, IntoColor, Srgb};
 
-const ITERATIONS: u32 = 50;
-const BASE_SCALE: f64 = 4.;
+mod animation;
+mod bookmarks;
+mod buddhabrot;
+mod coloring;
+mod headless;
+mod julia;
+mod newton;
+mod render;
+#[cfg(not(target_arch = "wasm32"))]
+mod terminal;
+
+use bookmarks::{Bookmark, BOOKMARKS_FILE};
+use buddhabrot::{channel_limits, Buddhabrot, Density};
+use coloring::{Coloring, Palette, PALETTES_FILE};
+use deep::{MAX_SCALE, MIN_SCALE};
+use formula::{formula, FORMULAS, MAX_DEGREE};
+use fractal::auto_iterations;
+use julia::{Julia, Parameter};
+use newton::{Polynomial, DEFAULT_POLYNOMIAL};
+use render::{Renderer, Reuse};
+use rust_mandelbrot::{deep, formula, fractal, viewport, BASE_CENTER, BASE_SCALE};
+use viewport::Viewport;
+
 const MOVE_SCALE: f64 = 8.;
+const ZOOM_FACTOR: f64 = 2.;
+
+const DENSITY_SPEED: f32 = 1.02;
+const OFFSET_SPEED: f32 = 0.005;
+const CYCLING_SPEED: f32 = 0.1;
+const RELAXATION_STEP: f64 = 0.05;
+const PLAYBACK_SPEED_FACTOR: f64 = 1.25;
+// Orbit points drawn under the mouse, the whole orbit is still iterated for the readout
+const ORBIT_DRAWN_POINTS: usize = 1000;
+
+// What the text typed at the bottom of the window is for
+#[derive(Clone, Copy, PartialEq)]
+enum Prompt {
+    Polynomial,
+    BookmarkName,
+}
+
+impl Prompt {
+    fn label(&self) -> &str {
+        match self {
+            Prompt::Polynomial => "polynomial",
+            Prompt::BookmarkName => "bookmark name",
+        }
+    }
+}
+
+fn clipboard_get() -> Option<String> {
+    unsafe { get_internal_gl() }.quad_context.clipboard_get()
+}
+
+fn clipboard_set(text: &str) {
+    unsafe { get_internal_gl() }
+        .quad_context
+        .clipboard_set(text)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("render") => headless::render(&args[1..]),
+        Some("animate") => animation::animate(&args[1..]),
+        #[cfg(not(target_arch = "wasm32"))]
+        Some("terminal") => terminal::explore(&args[1..]),
+        _ => {
+            macroquad::Window::new("rust-mandelbrot", explore());
+            Ok(())
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn explore() {
+    let mut viewport = Viewport::new(BASE_CENTER, BASE_SCALE);
+    let mut julia_viewport = Viewport::new(Complex::new(0., 0.), BASE_SCALE);
+
+    let mut last_viewport = None;
+    let mut last_julia_viewport = None;
+    let mut last_c = None;
+    let mut last_iterations = 0;
+
+    // None follows the zoom depth
+    let mut manual_iterations: Option<u32> = None;
+
+    let mut formula_index = 0;
+    let mut degree = 3;
+    let mut current_formula = formula(FORMULAS[formula_index], degree).unwrap();
+
+    let mut newton_mode = false;
+    let mut polynomial = Arc::new(Polynomial::parse(DEFAULT_POLYNOMIAL).unwrap());
+    let mut relaxation = 1.;
+
+    // Text being typed, and the last error or notice shown in its place
+    let mut input: Option<(Prompt, String)> = None;
+    let mut message: Option<String> = None;
+
+    // Parameter of the julia pane: under the mouse, pinned, or played back along a path
+    let mut julia = Julia::new();
 
-#[macroquad::main("rust-mandelbrot")]
-async fn main() {
-    let mut scale = BASE_SCALE;
-    let mut offset = (0., 0.);
+    let mut bookmarks = Bookmark::load_all(BOOKMARKS_FILE);
+    let mut bookmark_index: Option<usize> = None;
 
-    let mut last_scale = 0.;
-    let mut last_offset = (-1., -1.);
-    let mut last_mandelbrot_size = 0.;
-    let mut last_mouse_position = (-1., -1.);
+    // Orbit density mode of the left pane, None for the escape time
+    let mut density: Option<Density> = None;
 
-    let mut mandelbrot_texture = Texture2D::empty();
-    let mut julia_texture = Texture2D::empty();
+    let mut drag_position: Option<(f32, f32)> = None;
+    let mut selection_start: Option<(f32, f32)> = None;
+
+    let palettes = Palette::load_all(PALETTES_FILE);
+    let mut mandelbrot_palette = 0;
+    let mut julia_palette = 0;
+    let mut mandelbrot_coloring = Coloring::new(palettes[mandelbrot_palette].clone());
+    let mut julia_coloring = Coloring::new(palettes[julia_palette].clone());
+    let mut cycling = false;
+    let mut show_orbit = false;
+
+    let mut mandelbrot_renderer = Renderer::new();
+    let mut julia_renderer = Renderer::new();
+    let mut buddhabrot = Buddhabrot::new();
     loop {
         let (width, height) = (screen_width() as u32, screen_height() as u32);
-        let mandelbrot_size = (width / 2).min(height) as f64;
+        viewport.resize(width / 2, height);
+        julia_viewport.resize(width - width / 2, height);
+
+        let (mouse_x, mouse_y) = mouse_position();
+        let on_mandelbrot = viewport.contains(mouse_x as f64, mouse_y as f64);
+
+        // Keys go to the text input while typing
+        let typing = input.is_some();
+        let key_pressed = |key| !typing && is_key_pressed(key);
+        let key_down = |key| !typing && is_key_down(key);
+        let control = key_down(KeyCode::LeftControl) || key_down(KeyCode::RightControl);
+
+        // Text input, the typed characters are drained every frame and come newest first
+        let submitted = {
+            let mut chars = Vec::new();
+            while let Some(c) = get_char_pressed() {
+                chars.push(c);
+            }
+
+            let mut submitted = None;
+            if let Some((prompt, text)) = input.as_mut() {
+                text.extend(chars.into_iter().rev().filter(|c| !c.is_control()));
+                if is_key_pressed(KeyCode::Backspace) {
+                    text.pop();
+                } else if is_key_pressed(KeyCode::Escape) {
+                    input = None;
+                } else if is_key_pressed(KeyCode::Enter) {
+                    submitted = Some((*prompt, text.clone()));
+                    input = None;
+                }
+            }
+            submitted
+        };
 
         //Scroll
         {
-            if mouse_wheel().1 == -1. {
-                scale *= 2.;
-            } else if mouse_wheel().1 == 1. {
-                scale /= 2.;
+            let (zoom_x, zoom_y) = if on_mandelbrot {
+                (mouse_x as f64, mouse_y as f64)
+            } else {
+                (viewport.width as f64 / 2., viewport.height as f64 / 2.)
+            };
+
+            if mouse_wheel().1 < 0. {
+                viewport.zoom_at(zoom_x, zoom_y, ZOOM_FACTOR);
+            } else if mouse_wheel().1 > 0. {
+                viewport.zoom_at(zoom_x, zoom_y, 1. / ZOOM_FACTOR);
             }
         }
 
-        // Move
+        // Move, by whole pixels so the computed ones can be shifted
         {
-            if is_key_down(KeyCode::Left) {
-                offset.0 -= scale / MOVE_SCALE;
-            } else if is_key_down(KeyCode::Right) {
-                offset.0 += scale / MOVE_SCALE;
+            let step = (viewport.width.min(viewport.height) as f64 / MOVE_SCALE).round();
+            let mut delta = Complex::new(0., 0.);
+            if key_down(KeyCode::Left) {
+                delta.re -= step;
+            } else if key_down(KeyCode::Right) {
+                delta.re += step;
+            }
+            if key_down(KeyCode::Up) {
+                delta.im -= step;
+            } else if key_down(KeyCode::Down) {
+                delta.im += step;
             }
-            if is_key_down(KeyCode::Up) {
-                offset.1 -= scale / MOVE_SCALE;
-            } else if is_key_down(KeyCode::Down) {
-                offset.1 += scale / MOVE_SCALE;
+            if delta != Complex::new(0., 0.) {
+                viewport.translate(delta * viewport.pixel_size());
             }
         }
 
-        // Draw mandelbrot set
-        if scale != last_scale || mandelbrot_size != last_mandelbrot_size || offset != last_offset {
-            mandelbrot_texture = {
-                let bytes: Vec<u8> = (0..(mandelbrot_size.floor() as u32).pow(2))
-                    .flat_map(|i| {
-                        let (x, y) = (
-                            i % mandelbrot_size.floor() as u32,
-                            i / mandelbrot_size.floor() as u32,
-                        );
-                        let ni = {
-                            let c = Complex::new(
-                                x as f64 / mandelbrot_size * scale
-                                    - scale / 2.
-                                    - scale / MOVE_SCALE
-                                    + offset.0,
-                                y as f64 / mandelbrot_size * scale - scale / 2. + offset.1,
-                            );
-                            let mut z = Complex::new(0., 0.);
-
-                            let mut i = 0;
-                            while i < ITERATIONS && z.norm() < 2. {
-                                i += 1;
-                                z = z.powu(2) + c;
-                            }
-                            i
-                        };
-
-                        if ni == ITERATIONS {
-                            [0, 0, 0, 255]
-                        } else {
-                            let rgb: Srgb =
-                                Hsv::new(ni as f32 * 360. / ITERATIONS as f32, 1., 1.).into_color();
-                            [
-                                (rgb.red * 255.) as u8,
-                                (rgb.green * 255.) as u8,
-                                (rgb.blue * 255.) as u8,
-                                255,
-                            ]
-                        }
-                    })
-                    .collect();
-                Texture2D::from_rgba8(
-                    mandelbrot_size.floor() as u16,
-                    mandelbrot_size.floor() as u16,
-                    &bytes,
+        // Drag, holding control records a julia path instead, a click pins the parameter
+        {
+            let mouse_c = viewport.point_f64(mouse_x as f64, mouse_y as f64);
+            if is_mouse_button_pressed(MouseButton::Left) && on_mandelbrot {
+                if control {
+                    julia.start_recording(mouse_c, get_time());
+                } else {
+                    drag_position = Some((mouse_x, mouse_y));
+                }
+            } else if is_mouse_button_released(MouseButton::Left) {
+                drag_position = None;
+                julia.finish_recording();
+            } else if julia.recording() {
+                julia.record(mouse_c, get_time());
+            }
+
+            if let Some((last_x, last_y)) = drag_position {
+                if (mouse_x, mouse_y) != (last_x, last_y) {
+                    viewport.drag((mouse_x - last_x) as f64, (mouse_y - last_y) as f64);
+                    drag_position = Some((mouse_x, mouse_y));
+                }
+            }
+        }
+
+        // Zoom to selection
+        {
+            if is_mouse_button_pressed(MouseButton::Right) && on_mandelbrot {
+                selection_start = Some((mouse_x, mouse_y));
+            } else if is_mouse_button_released(MouseButton::Right) {
+                if let Some((start_x, start_y)) = selection_start.take() {
+                    viewport.zoom_to_rect(
+                        (start_x as f64, start_y as f64),
+                        (mouse_x as f64, mouse_y as f64),
+                    );
+                }
+            }
+        }
+
+        // Palette, holding shift controls the julia pane
+        let (mut mandelbrot_recolor, mut julia_recolor) = (false, false);
+        {
+            let julia_pane = key_down(KeyCode::LeftShift) || key_down(KeyCode::RightShift);
+            let (coloring, palette, recolor) = if julia_pane {
+                (&mut julia_coloring, &mut julia_palette, &mut julia_recolor)
+            } else {
+                (
+                    &mut mandelbrot_coloring,
+                    &mut mandelbrot_palette,
+                    &mut mandelbrot_recolor,
                 )
             };
+
+            if key_pressed(KeyCode::L) {
+                coloring.lighting = !coloring.lighting;
+                *recolor = true;
+            }
+            if key_pressed(KeyCode::P) {
+                *palette = (*palette + 1) % palettes.len();
+                coloring.palette = palettes[*palette].clone();
+                *recolor = true;
+            }
+            if key_pressed(KeyCode::K) {
+                coloring.mode = coloring.mode.next();
+                *recolor = true;
+            }
+            if key_pressed(KeyCode::I) {
+                coloring.interior = coloring.interior.next();
+                *recolor = true;
+            }
+            if key_down(KeyCode::RightBracket) {
+                coloring.density *= DENSITY_SPEED;
+                *recolor = true;
+            } else if key_down(KeyCode::LeftBracket) {
+                coloring.density /= DENSITY_SPEED;
+                *recolor = true;
+            }
+            if key_down(KeyCode::Equal) {
+                coloring.offset += OFFSET_SPEED;
+                *recolor = true;
+            } else if key_down(KeyCode::Minus) {
+                coloring.offset -= OFFSET_SPEED;
+                *recolor = true;
+            }
         }
 
-        // Draw julia
-        if scale != last_scale
-            || mandelbrot_size != last_mandelbrot_size
-            || mouse_position() != last_mouse_position
-            || offset != last_offset
+        // Color cycling
         {
-            julia_texture = {
-                let (mouse_x, mouse_y) = mouse_position();
-                let c = Complex::new(
-                    mouse_x as f64 / mandelbrot_size * scale - scale / 2. - scale / MOVE_SCALE
-                        + offset.0,
-                    mouse_y as f64 / mandelbrot_size * scale - scale / 2. + offset.1,
-                );
-                let bytes: Vec<u8> = (0..(mandelbrot_size as u32).pow(2))
-                    .flat_map(|i| {
-                        let (x, y) = (
-                            i % mandelbrot_size.floor() as u32,
-                            i / mandelbrot_size.floor() as u32,
-                        );
-                        let ni = {
-                            let mut z = Complex::new(
-                                x as f64 / mandelbrot_size * BASE_SCALE - BASE_SCALE / 2.,
-                                y as f64 / mandelbrot_size * BASE_SCALE - BASE_SCALE / 2.,
-                            );
-
-                            let mut i = 0;
-                            while i < ITERATIONS && z.norm() < 2. {
-                                i += 1;
-                                z = z.powu(2) + c;
-                            }
-                            i
-                        };
-
-                        if ni == ITERATIONS {
-                            [0, 0, 0, 255]
-                        } else {
-                            let rgb: Srgb =
-                                Hsv::new(ni as f32 * 360. / ITERATIONS as f32, 1., 1.).into_color();
-                            [
-                                (rgb.red * 255.) as u8,
-                                (rgb.green * 255.) as u8,
-                                (rgb.blue * 255.) as u8,
-                                255,
-                            ]
+            if key_pressed(KeyCode::C) && !control {
+                cycling = !cycling;
+            }
+            if cycling {
+                mandelbrot_coloring.offset += CYCLING_SPEED * get_frame_time();
+                julia_coloring.offset += CYCLING_SPEED * get_frame_time();
+                mandelbrot_recolor = true;
+                julia_recolor = true;
+            }
+        }
+
+        // Bookmarks
+        let bookmark_loaded = {
+            let mut load = None;
+            if key_pressed(KeyCode::Period) && !bookmarks.is_empty() {
+                let index = bookmark_index.map_or(0, |i| (i + 1) % bookmarks.len());
+                bookmark_index = Some(index);
+                load = Some(bookmarks[index].clone());
+            } else if key_pressed(KeyCode::Comma) && !bookmarks.is_empty() {
+                let index = bookmark_index.map_or(0, |i| i + bookmarks.len() - 1) % bookmarks.len();
+                bookmark_index = Some(index);
+                load = Some(bookmarks[index].clone());
+            } else if control && key_pressed(KeyCode::V) {
+                match clipboard_get().map(|share| Bookmark::from_share(&share)) {
+                    Some(Ok(bookmark)) => load = Some(bookmark),
+                    Some(Err(err)) => message = Some(err),
+                    None => message = Some("Empty clipboard".to_string()),
+                }
+            }
+
+            let current = |name: &str| Bookmark {
+                name: name.to_string(),
+                center: viewport.center.to_decimal(viewport.pixel_size()),
+                scale: viewport.scale,
+                iterations: manual_iterations,
+                formula: FORMULAS[formula_index].to_string(),
+                degree,
+                palette: mandelbrot_coloring.palette.name.clone(),
+                julia: match julia.parameter {
+                    Parameter::Pinned(c) => Some((c.re, c.im)),
+                    _ => None,
+                },
+            };
+            if control && key_pressed(KeyCode::C) {
+                let share = current("").to_share();
+                clipboard_set(&share);
+                message = Some(format!("Copied {}", share));
+            } else if key_pressed(KeyCode::S) {
+                input = Some((Prompt::BookmarkName, String::new()));
+            } else if let Some((Prompt::BookmarkName, name)) = &submitted {
+                bookmarks.push(current(name));
+                bookmark_index = Some(bookmarks.len() - 1);
+                message = Some(match Bookmark::save_all(BOOKMARKS_FILE, &bookmarks) {
+                    Ok(()) => format!("Saved {} to {}", name, BOOKMARKS_FILE),
+                    Err(err) => err,
+                });
+            }
+
+            if let Some(bookmark) = &load {
+                let formula = FORMULAS
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(&bookmark.formula));
+                let palette = palettes
+                    .iter()
+                    .position(|palette| palette.name.eq_ignore_ascii_case(&bookmark.palette));
+                match (bookmark.center(), formula) {
+                    (Ok(center), Some(formula)) => {
+                        viewport.center = center;
+                        viewport.scale = bookmark.scale.clamp(MIN_SCALE, MAX_SCALE);
+                        manual_iterations = bookmark.iterations;
+                        formula_index = formula;
+                        degree = bookmark.degree.clamp(2, MAX_DEGREE);
+                        julia.pin(bookmark.julia());
+                        if let Some(palette) = palette {
+                            mandelbrot_palette = palette;
+                            mandelbrot_coloring.palette = palettes[palette].clone();
+                            mandelbrot_recolor = true;
                         }
-                    })
-                    .collect();
-                Texture2D::from_rgba8(
-                    mandelbrot_size.floor() as u16,
-                    mandelbrot_size.floor() as u16,
-                    &bytes,
-                )
+                        message = Some(bookmark.name.clone());
+                    }
+                    (Err(err), _) => message = Some(err),
+                    (_, None) => message = Some(format!("Unknown formula {}", bookmark.formula)),
+                }
+            }
+            load.is_some()
+        };
+
+        // Julia parameter
+        {
+            if key_pressed(KeyCode::J) {
+                julia.pin(match julia.parameter {
+                    Parameter::Mouse => Some(viewport.point_f64(mouse_x as f64, mouse_y as f64)),
+                    _ => None,
+                });
+            } else if key_pressed(KeyCode::O) {
+                julia.toggle_circle();
+            }
+
+            if key_pressed(KeyCode::Key9) {
+                julia.speed /= PLAYBACK_SPEED_FACTOR;
+            } else if key_pressed(KeyCode::Key0) {
+                julia.speed *= PLAYBACK_SPEED_FACTOR;
+            }
+            julia.advance(get_frame_time() as f64);
+        }
+
+        // Iterations
+        let iterations = {
+            let iterations = manual_iterations.unwrap_or_else(|| auto_iterations(viewport.scale));
+            if key_pressed(KeyCode::PageUp) {
+                manual_iterations = Some(iterations.saturating_mul(2));
+            } else if key_pressed(KeyCode::PageDown) {
+                manual_iterations = Some((iterations / 2).max(1));
+            } else if key_pressed(KeyCode::Home) {
+                manual_iterations = None;
+            }
+            manual_iterations.unwrap_or_else(|| auto_iterations(viewport.scale))
+        };
+
+        // Formula
+        let formula_changed = {
+            let mut changed = bookmark_loaded;
+            if key_pressed(KeyCode::F) {
+                formula_index = (formula_index + 1) % FORMULAS.len();
+                changed = true;
+            }
+            if key_pressed(KeyCode::D) {
+                degree = if degree >= MAX_DEGREE { 2 } else { degree + 1 };
+                changed = true;
+            }
+            if changed {
+                current_formula = formula(FORMULAS[formula_index], degree).unwrap();
+            }
+            changed
+        };
+
+        // Newton
+        let newton_changed = {
+            let mut changed = false;
+            if key_pressed(KeyCode::N) {
+                newton_mode = !newton_mode;
+                changed = true;
+            }
+            if newton_mode && key_pressed(KeyCode::R) {
+                if key_down(KeyCode::LeftShift) || key_down(KeyCode::RightShift) {
+                    relaxation -= RELAXATION_STEP;
+                } else {
+                    relaxation += RELAXATION_STEP;
+                }
+                changed = true;
+            }
+            if key_pressed(KeyCode::E) {
+                input = Some((Prompt::Polynomial, String::new()));
+            } else if let Some((Prompt::Polynomial, text)) = &submitted {
+                match Polynomial::parse(text) {
+                    Ok(parsed) => {
+                        polynomial = Arc::new(parsed);
+                        newton_mode = true;
+                        changed = true;
+                    }
+                    Err(err) => message = Some(err),
+                }
+            }
+            changed
+        };
+
+        // Density
+        let density_changed = key_pressed(KeyCode::B);
+        if density_changed {
+            density = match density {
+                None => Some(Density::Buddhabrot),
+                Some(Density::Buddhabrot) => Some(Density::AntiBuddhabrot),
+                Some(Density::AntiBuddhabrot) => None,
             };
         }
+        // Newton mode has no orbits to accumulate
+        let active_density = density.filter(|_| !newton_mode);
 
-        last_scale = scale;
-        last_offset = offset;
-        last_mouse_position = mouse_position();
-        last_mandelbrot_size = mandelbrot_size;
+        // Draw mandelbrot set
+        if last_viewport.as_ref() != Some(&viewport)
+            || iterations != last_iterations
+            || formula_changed
+            || newton_changed
+            || density_changed
+        {
+            if let Some(density) = active_density {
+                buddhabrot.start(
+                    &viewport,
+                    &current_formula,
+                    density,
+                    channel_limits(iterations),
+                );
+            } else {
+                buddhabrot.stop();
 
-        draw_texture(mandelbrot_texture, 0., 0., WHITE);
-        draw_texture(julia_texture, mandelbrot_size.ceil() as f32, 0., WHITE);
+                let reuse = if newton_changed || density_changed || formula_changed {
+                    Reuse::Nothing
+                } else {
+                    Reuse::iterations(last_iterations, iterations)
+                };
+                if newton_mode {
+                    let sampler = newton::newton(
+                        &viewport,
+                        &polynomial,
+                        relaxation,
+                        Complex::new(0., 0.),
+                        iterations,
+                    );
+                    mandelbrot_renderer.start(&viewport, move |_| Some(sampler), reuse);
+                } else {
+                    // Deep zooms compute their reference orbit on the workers
+                    let (view, formula) = (viewport.clone(), current_formula.clone());
+                    mandelbrot_renderer.start(
+                        &viewport,
+                        move |cancelled| {
+                            fractal::mandelbrot(&view, &formula, iterations, cancelled)
+                        },
+                        reuse,
+                    );
+                }
+            }
+        }
 
-        // Number
+        // Draw julia
+        let c = julia.c(viewport.point_f64(mouse_x as f64, mouse_y as f64));
+        if Some(c) != last_c
+            || last_julia_viewport.as_ref() != Some(&julia_viewport)
+            || iterations != last_iterations
+            || formula_changed
+            || newton_changed
         {
-            let (mouse_x, mouse_y) = mouse_position();
-            let c = Complex::new(
-                mouse_x as f64 / mandelbrot_size * scale - scale / 2. + offset.0,
-                mouse_y as f64 / mandelbrot_size * scale - scale / 2. + offset.1,
+            // The nova julia set in Newton mode
+            let sampler = if newton_mode {
+                newton::newton(&julia_viewport, &polynomial, relaxation, c, iterations)
+            } else {
+                fractal::julia(&julia_viewport, &current_formula, c, iterations)
+            };
+            let reuse = if Some(c) != last_c || newton_changed || formula_changed {
+                Reuse::Nothing
+            } else {
+                Reuse::iterations(last_iterations, iterations)
+            };
+            julia_renderer.start(&julia_viewport, move |_| Some(sampler), reuse);
+            last_c = Some(c);
+        }
+
+        last_viewport = Some(viewport.clone());
+        last_julia_viewport = Some(julia_viewport.clone());
+        last_iterations = iterations;
+
+        if mandelbrot_recolor {
+            mandelbrot_renderer.recolor(&mandelbrot_coloring);
+        }
+        if julia_recolor {
+            julia_renderer.recolor(&julia_coloring);
+        }
+        mandelbrot_renderer.update(&mandelbrot_coloring);
+        julia_renderer.update(&julia_coloring);
+
+        // The palette density sets the exposure of the orbit densities
+        if active_density.is_some() {
+            buddhabrot.update(mandelbrot_coloring.density);
+            draw_texture(buddhabrot.texture(), 0., 0., WHITE);
+        } else {
+            draw_texture(mandelbrot_renderer.texture(), 0., 0., WHITE);
+        }
+        draw_texture(julia_renderer.texture(), viewport.width as f32, 0., WHITE);
+
+        // Selection
+        if let Some((start_x, start_y)) = selection_start {
+            draw_rectangle_lines(
+                start_x.min(mouse_x),
+                start_y.min(mouse_y),
+                (mouse_x - start_x).abs(),
+                (mouse_y - start_y).abs(),
+                1.,
+                WHITE,
             );
+        }
+
+        // Orbit of the point under the mouse over its pane, the cycle it settled on is circled
+        if key_pressed(KeyCode::T) {
+            show_orbit = !show_orbit;
+        }
+        let orbit = if show_orbit && !newton_mode {
+            let (pane, offset, orbit) = if on_mandelbrot {
+                let point = viewport.point_f64(mouse_x as f64, mouse_y as f64);
+                let orbit = fractal::orbit(
+                    current_formula.as_ref(),
+                    Complex::new(0., 0.),
+                    point,
+                    iterations,
+                );
+                (&viewport, 0., orbit)
+            } else {
+                let point = julia_viewport
+                    .point_f64((mouse_x - viewport.width as f32) as f64, mouse_y as f64);
+                let orbit = fractal::orbit(current_formula.as_ref(), point, c, iterations);
+                (&julia_viewport, viewport.width as f32, orbit)
+            };
+
+            let pixel = |z| {
+                let (x, y) = pane.pixel(z);
+                (x as f32 + offset, y as f32)
+            };
+            let points: Vec<(f32, f32)> = orbit
+                .points
+                .iter()
+                .take(ORBIT_DRAWN_POINTS)
+                .map(|&z| pixel(z))
+                .collect();
+            points.windows(2).for_each(|segment| {
+                let ((x1, y1), (x2, y2)) = (segment[0], segment[1]);
+                draw_line(x1, y1, x2, y2, 1., YELLOW);
+            });
+            if let Some(period) = orbit.period {
+                orbit.points[orbit.points.len() - period..]
+                    .iter()
+                    .for_each(|&z| {
+                        let (x, y) = pixel(z);
+                        draw_circle_lines(x, y, 4., 1., RED);
+                    });
+            }
+            Some(orbit)
+        } else {
+            None
+        };
+
+        // Number
+        {
+            let (re, im) = viewport
+                .point(mouse_x as f64, mouse_y as f64)
+                .to_decimal(viewport.pixel_size());
             draw_text(
-                &format!("c = {} + {}i", c.re, c.im),
-                mandelbrot_size as f32,
+                &match julia.parameter {
+                    Parameter::Mouse => format!("c = {} + {}i", re, im),
+                    _ => format!(
+                        "c = {} + {}i, julia c = {:.6} + {:.6}i ({})",
+                        re,
+                        im,
+                        c.re,
+                        c.im,
+                        julia.describe()
+                    ),
+                },
+                viewport.width as f32,
                 20.,
                 16.,
                 WHITE,
-            )
+            );
+            draw_text(
+                &format!(
+                    "coloring = {} / {}",
+                    mandelbrot_coloring.describe(),
+                    julia_coloring.describe()
+                ),
+                viewport.width as f32,
+                40.,
+                16.,
+                WHITE,
+            );
+            draw_text(
+                &format!(
+                    "{}, iterations = {} ({})",
+                    if newton_mode {
+                        format!("Newton {}, relaxation = {:.2}", polynomial.text, relaxation)
+                    } else {
+                        current_formula.name()
+                    },
+                    iterations,
+                    if manual_iterations.is_some() {
+                        "manual"
+                    } else {
+                        "auto"
+                    }
+                ),
+                viewport.width as f32,
+                60.,
+                16.,
+                WHITE,
+            );
+            if let Some(density) = active_density {
+                let [red, green, blue] = channel_limits(iterations);
+                draw_text(
+                    &format!(
+                        "{}, limits = {} / {} / {}, samples = {}",
+                        density.name(),
+                        red,
+                        green,
+                        blue,
+                        buddhabrot.samples()
+                    ),
+                    viewport.width as f32,
+                    80.,
+                    16.,
+                    WHITE,
+                );
+            }
+            if let Some(orbit) = &orbit {
+                let steps = orbit.points.len() - 1;
+                draw_text(
+                    &match (orbit.escaped, orbit.period) {
+                        (Some(i), _) => format!("orbit escapes after {} iterations", i),
+                        (None, Some(period)) => {
+                            format!(
+                                "orbit bounded after {} iterations, period {}",
+                                steps, period
+                            )
+                        }
+                        (None, None) => {
+                            format!("orbit bounded after {} iterations, no period found", steps)
+                        }
+                    },
+                    viewport.width as f32,
+                    if active_density.is_some() { 100. } else { 80. },
+                    16.,
+                    WHITE,
+                );
+            }
+        }
+
+        // Text input
+        {
+            let line = match (&input, &message) {
+                (Some((prompt, text)), _) => Some(format!("{}> {}_", prompt.label(), text)),
+                (None, Some(message)) => Some(message.clone()),
+                (None, None) => None,
+            };
+            if let Some(line) = line {
+                draw_text(&line, 10., height as f32 - 10., 20., WHITE);
+            }
         }
 
         next_frame().await
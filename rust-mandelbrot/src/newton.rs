@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use num_complex::Complex;
+
+use crate::fractal::{Sample, Sampler};
+use crate::viewport::Viewport;
+
+pub const DEFAULT_POLYNOMIAL: &str = "z^3 - 1";
+
+// Step length under which Newton's method is considered settled
+const TOLERANCE: f64 = 1e-6;
+// Distance under which a settled point is attributed to a root
+const ROOT_DISTANCE: f64 = 1e-3;
+const ROOT_FINDING_STEPS: usize = 500;
+
+fn parse_complex(text: &str) -> Result<Complex<f64>, String> {
+    let trimmed = text
+        .trim()
+        .trim_start_matches('+')
+        .trim_start_matches('(')
+        .trim_end_matches(')');
+    trimmed
+        .parse()
+        .map_err(|_| format!("Invalid number {}", text))
+}
+
+// Split at the top level + and - signs, keeping the signs with their terms
+fn split_terms(text: &str) -> Vec<String> {
+    let mut terms = vec![String::new()];
+    let mut depth = 0;
+    let mut previous = ' ';
+
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '+' | '-'
+                if depth == 0
+                    && !terms.last().unwrap().is_empty()
+                    && !matches!(previous, 'e' | 'E' | '^') =>
+            {
+                terms.push(String::new())
+            }
+            _ => {}
+        }
+        terms.last_mut().unwrap().push(c);
+        previous = c;
+    }
+
+    terms.into_iter().filter(|term| !term.is_empty()).collect()
+}
+
+pub struct Polynomial {
+    pub text: String,
+    // Coefficient of z^i at index i
+    coefficients: Vec<Complex<f64>>,
+    pub roots: Vec<Complex<f64>>,
+}
+
+impl Polynomial {
+    // Either a polynomial in z, "z^3 - 2z + (1+i)", or its roots, "roots: 1, -0.5+0.87i"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+
+        if let Some(roots) = text.strip_prefix("roots:") {
+            let roots = roots
+                .split(',')
+                .map(parse_complex)
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(Self::from_roots(text, roots));
+        }
+
+        let mut coefficients: Vec<Complex<f64>> = Vec::new();
+        for term in split_terms(text) {
+            let (coefficient, power) = match term.find('z') {
+                None => (parse_complex(&term)?, 0),
+                Some(position) => {
+                    let coefficient = term[..position].trim_end_matches('*');
+                    let coefficient = match coefficient {
+                        "" | "+" => Complex::new(1., 0.),
+                        "-" => Complex::new(-1., 0.),
+                        _ => match coefficient.strip_prefix('-') {
+                            Some(rest) if rest.starts_with('(') => -parse_complex(rest)?,
+                            _ => parse_complex(coefficient)?,
+                        },
+                    };
+                    let power = match term[position + 1..].strip_prefix('^') {
+                        Some(power) => power
+                            .parse()
+                            .map_err(|_| format!("Invalid power in {}", term))?,
+                        None if position + 1 == term.len() => 1,
+                        None => return Err(format!("Invalid term {}", term)),
+                    };
+                    (coefficient, power)
+                }
+            };
+
+            if coefficients.len() <= power {
+                coefficients.resize(power + 1, Complex::new(0., 0.));
+            }
+            coefficients[power] += coefficient;
+        }
+
+        while coefficients.last() == Some(&Complex::new(0., 0.)) {
+            coefficients.pop();
+        }
+        if coefficients.len() < 2 {
+            return Err(format!("{} has no roots", text));
+        }
+
+        let roots = find_roots(&coefficients);
+        Ok(Self {
+            text: text.to_string(),
+            coefficients,
+            roots,
+        })
+    }
+
+    fn from_roots(text: &str, roots: Vec<Complex<f64>>) -> Self {
+        // Expand (z - r0)(z - r1)...
+        let mut coefficients = vec![Complex::new(1., 0.)];
+        roots.iter().for_each(|root| {
+            let mut next = vec![Complex::new(0., 0.); coefficients.len() + 1];
+            coefficients
+                .iter()
+                .enumerate()
+                .for_each(|(i, coefficient)| {
+                    next[i + 1] += coefficient;
+                    next[i] -= coefficient * root;
+                });
+            coefficients = next;
+        });
+
+        Self {
+            text: text.to_string(),
+            coefficients,
+            roots,
+        }
+    }
+
+    // Value and derivative at z
+    pub fn eval(&self, z: Complex<f64>) -> (Complex<f64>, Complex<f64>) {
+        self.coefficients.iter().rev().fold(
+            (Complex::new(0., 0.), Complex::new(0., 0.)),
+            |(p, dp), coefficient| (p * z + coefficient, dp * z + p),
+        )
+    }
+}
+
+// Durand-Kerner iteration on the monic polynomial
+fn find_roots(coefficients: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    let degree = coefficients.len() - 1;
+    let leading = coefficients[degree];
+    let monic: Vec<Complex<f64>> = coefficients.iter().map(|c| c / leading).collect();
+    let eval = |z: Complex<f64>| {
+        monic
+            .iter()
+            .rev()
+            .fold(Complex::new(0., 0.), |p, c| p * z + c)
+    };
+
+    let mut roots: Vec<Complex<f64>> = (0..degree)
+        .map(|i| Complex::new(0.4, 0.9).powu(i as u32))
+        .collect();
+    for _ in 0..ROOT_FINDING_STEPS {
+        (0..degree).for_each(|i| {
+            let denominator = (0..degree)
+                .filter(|&j| j != i)
+                .fold(Complex::new(1., 0.), |d, j| d * (roots[i] - roots[j]));
+            let correction = eval(roots[i]) / denominator;
+            roots[i] -= correction;
+        });
+    }
+    roots
+}
+
+// Generalized Newton's method z -> z - relaxation * p(z) / p'(z) + c from z,
+// c = 0 gives the plain root basins and other values the Nova julia sets
+pub fn newton_steps(
+    polynomial: &Polynomial,
+    mut z: Complex<f64>,
+    c: Complex<f64>,
+    relaxation: f64,
+    iterations: u32,
+) -> Sample {
+    let mut last_step = f64::INFINITY;
+
+    for i in 1..=iterations {
+        let (p, dp) = polynomial.eval(z);
+        if dp == Complex::new(0., 0.) {
+            return Sample::Inside(None);
+        }
+
+        let next = z - relaxation * p / dp + c;
+        let step = (next - z).norm();
+        z = next;
+
+        if step < TOLERANCE {
+            // Fraction of the last step spent above the tolerance, smooths the shading
+            let fraction = if last_step.is_finite() && step > 0. {
+                ((TOLERANCE / step).ln() / (last_step / step).ln()).clamp(0., 1.)
+            } else {
+                0.
+            };
+            let basin = polynomial
+                .roots
+                .iter()
+                .position(|root| (z - root).norm() < ROOT_DISTANCE)
+                .map(|root| root as f32 / polynomial.roots.len() as f32);
+
+            return Sample::Converged {
+                basin,
+                steps: i as f32 - fraction as f32,
+            };
+        }
+        last_step = step;
+    }
+
+    Sample::Inside(None)
+}
+
+pub fn newton(
+    viewport: &Viewport,
+    polynomial: &Arc<Polynomial>,
+    relaxation: f64,
+    c: Complex<f64>,
+    iterations: u32,
+) -> Sampler {
+    let viewport = viewport.clone();
+    let polynomial = polynomial.clone();
+    Sampler::new(move |x, y| {
+        newton_steps(
+            &polynomial,
+            viewport.point_f64(x, y),
+            c,
+            relaxation,
+            iterations,
+        )
+    })
+}
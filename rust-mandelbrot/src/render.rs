@@ -0,0 +1,422 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+
+use macroquad::prelude::*;
+use rayon::prelude::*;
+
+use crate::coloring::{Coloring, Histogram, Mode};
+use crate::fractal::{Sample, Sampler};
+use crate::viewport::Viewport;
+
+const TILE_SIZE: u32 = 64;
+// Pixel spacing of the first pass, halved by each following pass down to 1
+const PREVIEW_STEP: u32 = 8;
+// Distance in pixels under which a new pixel is the same point as a previous one
+const REUSE_EPSILON: f64 = 1e-3;
+// Seconds between two histogram equalizations while tiles arrive
+const EQUALIZE_INTERVAL: f64 = 0.25;
+// Seconds of each frame spent on the tiles where there are no threads to compute them
+#[cfg(target_arch = "wasm32")]
+const FRAME_BUDGET: f64 = 0.01;
+
+// Builds the sampler of a render on the workers, as the reference orbit of a deep zoom
+// takes a while, none when the render is cancelled first
+type Build = Box<dyn FnOnce(&AtomicBool) -> Option<Sampler> + Send>;
+
+// What the values of the previous image still hold for a new sampler
+#[derive(Clone, Copy, PartialEq)]
+pub enum Reuse {
+    // A different fractal
+    Nothing,
+    // Only shown until the new values arrive
+    Preview,
+    // The iteration budget grew, samples that escaped or converged before it are final
+    Finished,
+    // The same fractal
+    All,
+}
+
+impl Reuse {
+    // Reuse between two iteration budgets of the same fractal
+    pub fn iterations(previous: u32, iterations: u32) -> Self {
+        match iterations.cmp(&previous) {
+            std::cmp::Ordering::Equal => Reuse::All,
+            std::cmp::Ordering::Greater => Reuse::Finished,
+            std::cmp::Ordering::Less => Reuse::Preview,
+        }
+    }
+}
+
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    // Spacing of the pass, each sample covers a step x step block until refined
+    step: u32,
+    // Pixels computed by this pass, the ones of the previous passes are left out
+    samples: Vec<(u32, u32, Sample)>,
+}
+
+// Passes of a render, coarse to fine, step being the spacing of the current one
+struct Passes {
+    // Pixels of a previous image that are left out
+    known: Vec<bool>,
+    width: u32,
+    height: u32,
+    tiles: Vec<(u32, u32)>,
+    step: u32,
+}
+
+impl Passes {
+    fn new(known: Vec<bool>, width: u32, height: u32) -> Self {
+        let tiles = (0..height)
+            .step_by(TILE_SIZE as usize)
+            .flat_map(|y| (0..width).step_by(TILE_SIZE as usize).map(move |x| (x, y)))
+            .collect();
+        Self {
+            known,
+            width,
+            height,
+            tiles,
+            step: PREVIEW_STEP,
+        }
+    }
+
+    // Pixels of a tile computed by the current pass, none once the render is cancelled
+    fn tile(&self, sampler: &Sampler, (x, y): (u32, u32), cancelled: &AtomicBool) -> Option<Tile> {
+        let (width, step) = (self.width, self.step);
+        let (tile_width, tile_height) = (TILE_SIZE.min(width - x), TILE_SIZE.min(self.height - y));
+        let mut samples = Vec::new();
+        let previous = step * 2;
+
+        for py in (y..y + tile_height).step_by(step as usize) {
+            if cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            // Left out when already computed by a coarser pass or a previous image
+            let row: Vec<u32> = (x..x + tile_width)
+                .step_by(step as usize)
+                .filter(|px| {
+                    !(step < PREVIEW_STEP && px % previous == 0 && py % previous == 0
+                        || self.known[(py * width + px) as usize])
+                })
+                .collect();
+            let points: Vec<(f64, f64)> = row.iter().map(|&px| (px as f64, py as f64)).collect();
+            samples.extend(
+                row.into_iter()
+                    .zip(sampler.samples(&points))
+                    .map(|(px, sample)| (px, py, sample)),
+            );
+        }
+
+        Some(Tile {
+            x,
+            y,
+            width: tile_width,
+            height: tile_height,
+            step,
+            samples,
+        })
+    }
+}
+
+// Passes left to compute from the main loop, next being the index of the next tile
+#[cfg(target_arch = "wasm32")]
+struct Pending {
+    // Built in the first frame of the render, then used by the passes
+    build: Option<Build>,
+    sampler: Option<Sampler>,
+    passes: Passes,
+    next: usize,
+    sender: std::sync::mpsc::Sender<Tile>,
+}
+
+// Computes textures tile by tile on the rayon thread pool, coarse to fine,
+// the finished tiles are colored and uploaded into the texture from the main loop
+pub struct Renderer {
+    texture: Texture2D,
+    width: u32,
+    height: u32,
+    // Viewport of the values, to find them again once it moves
+    viewport: Option<Viewport>,
+    values: Vec<Sample>,
+    // Pixels whose value is final, the others hold a preview
+    known: Vec<bool>,
+    // Whether the values moved and the whole texture has to be uploaded again
+    moved: bool,
+    tiles: Option<Receiver<Tile>>,
+    // Set to stop the workers of a render that has been replaced
+    cancelled: Arc<AtomicBool>,
+    // Histogram of the values for the histogram coloring, stale once new tiles arrived
+    histogram: Option<Histogram>,
+    stale: bool,
+    equalized: f64,
+    // Without threads, the render is computed a bit on every update
+    #[cfg(target_arch = "wasm32")]
+    pending: Option<Pending>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            texture: Texture2D::empty(),
+            width: 0,
+            height: 0,
+            viewport: None,
+            values: Vec::new(),
+            known: Vec::new(),
+            moved: false,
+            tiles: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            histogram: None,
+            stale: false,
+            equalized: 0.,
+            #[cfg(target_arch = "wasm32")]
+            pending: None,
+        }
+    }
+
+    pub fn texture(&self) -> Texture2D {
+        self.texture
+    }
+
+    // Pixel size of the values, which the distance estimation is measured against
+    fn pixel_size(&self) -> f64 {
+        self.viewport.as_ref().map_or(1., Viewport::pixel_size)
+    }
+
+    // Values of the previous image where the new viewport shows the same points: exact on a
+    // pan or when the pixels line up after a zoom, nearest ones as a preview otherwise
+    fn reproject(
+        &self,
+        previous: &Viewport,
+        viewport: &Viewport,
+        reuse: Reuse,
+    ) -> (Vec<Sample>, Vec<bool>) {
+        let center = viewport.center.difference(&previous.center);
+        let (values, known) = (&self.values, &self.known);
+
+        (0..viewport.width * viewport.height)
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = (i % viewport.width, i / viewport.width);
+                let (px, py) = previous.delta_pixel(viewport.delta(x as f64, y as f64) + center);
+                let (nx, ny) = (px.round(), py.round());
+
+                if !previous.contains(nx, ny) {
+                    return (Sample::Inside(None), false);
+                }
+                let index = (ny as u32 * previous.width + nx as u32) as usize;
+                let sample = values[index];
+                let exact = (px - nx).abs() < REUSE_EPSILON && (py - ny).abs() < REUSE_EPSILON;
+                let valid = match reuse {
+                    Reuse::All => true,
+                    Reuse::Finished => !matches!(sample, Sample::Inside(_)),
+                    Reuse::Nothing | Reuse::Preview => false,
+                };
+                (sample, exact && valid && known[index])
+            })
+            .unzip()
+    }
+
+    // Start rendering a new image, the previous one is cancelled and its tiles in flight dropped,
+    // the pixels already computed are kept as far as reuse allows
+    pub fn start(
+        &mut self,
+        viewport: &Viewport,
+        sampler: impl FnOnce(&AtomicBool) -> Option<Sampler> + Send + 'static,
+        reuse: Reuse,
+    ) {
+        let (width, height) = (viewport.width, viewport.height);
+
+        match &self.viewport {
+            Some(previous) if reuse == Reuse::All && previous == viewport => {}
+            Some(previous) if reuse != Reuse::Nothing => {
+                (self.values, self.known) = self.reproject(previous, viewport, reuse);
+                self.moved = true;
+            }
+            _ => {
+                // At the same size the previous image stays as a preview
+                if (width, height) != (self.width, self.height) {
+                    self.values = vec![Sample::Inside(None); (width * height) as usize];
+                }
+                self.known = vec![false; (width * height) as usize];
+            }
+        }
+        self.viewport = Some(viewport.clone());
+
+        if (width, height) != (self.width, self.height) {
+            if self.width * self.height != 0 {
+                self.texture.delete();
+            }
+            self.texture = Texture2D::from_rgba8(
+                width as u16,
+                height as u16,
+                &vec![0; (width * height * 4) as usize],
+            );
+            self.width = width;
+            self.height = height;
+        }
+
+        let (sender, receiver) = channel();
+        self.tiles = Some(receiver);
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.cancelled = Arc::new(AtomicBool::new(false));
+        let passes = Passes::new(self.known.clone(), width, height);
+        let build: Build = Box::new(sampler);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let cancelled = self.cancelled.clone();
+            rayon::spawn(move || {
+                let Some(sampler) = build(&cancelled) else {
+                    return;
+                };
+                // Passes run one after the other so a tile is never refined before its preview
+                let mut passes = passes;
+                while passes.step >= 1 && !cancelled.load(Ordering::Relaxed) {
+                    passes
+                        .tiles
+                        .par_iter()
+                        .for_each_with(sender.clone(), |sender, &tile| {
+                            // The receiver is gone when a newer render has started
+                            if let Some(tile) = passes.tile(&sampler, tile, &cancelled) {
+                                let _ = sender.send(tile);
+                            }
+                        });
+                    passes.step /= 2;
+                }
+            });
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.pending = Some(Pending {
+                build: Some(build),
+                sampler: None,
+                passes,
+                next: 0,
+                sender,
+            });
+        }
+    }
+
+    // Compute tiles in order for a share of the frame, on targets without threads
+    #[cfg(target_arch = "wasm32")]
+    fn work(&mut self) {
+        let Some(pending) = &mut self.pending else {
+            return;
+        };
+        // Without threads the sampler takes a frame of its own
+        if let Some(build) = pending.build.take() {
+            pending.sampler = build(&self.cancelled);
+            return;
+        }
+        let Some(sampler) = &pending.sampler else {
+            self.pending = None;
+            return;
+        };
+        let start = get_time();
+        while pending.passes.step >= 1 && get_time() - start < FRAME_BUDGET {
+            match pending.passes.tiles.get(pending.next) {
+                Some(&tile) => {
+                    if let Some(tile) = pending.passes.tile(sampler, tile, &self.cancelled) {
+                        let _ = pending.sender.send(tile);
+                    }
+                    pending.next += 1;
+                }
+                None => {
+                    pending.passes.step /= 2;
+                    pending.next = 0;
+                }
+            }
+        }
+        if pending.passes.step == 0 {
+            self.pending = None;
+        }
+    }
+
+    // Upload every tile finished since the last frame
+    pub fn update(&mut self, coloring: &Coloring) {
+        #[cfg(target_arch = "wasm32")]
+        self.work();
+
+        if self.moved {
+            self.moved = false;
+            self.recolor(coloring);
+        }
+
+        let mut arrived = false;
+        let pixel_size = self.pixel_size();
+        if let Some(tiles) = &self.tiles {
+            tiles.try_iter().for_each(|tile| {
+                arrived = true;
+                // Each sample fills the pixels of its block that are not final yet,
+                // the blocks of the coarser samples already hold their values
+                let (right, bottom) = (tile.x + tile.width, tile.y + tile.height);
+                tile.samples.iter().for_each(|&(x, y, sample)| {
+                    (y..(y + tile.step).min(bottom)).for_each(|py| {
+                        (x..(x + tile.step).min(right)).for_each(|px| {
+                            let index = (py * self.width + px) as usize;
+                            if !self.known[index] {
+                                self.values[index] = sample;
+                            }
+                        });
+                    });
+                    self.known[(y * self.width + x) as usize] = true;
+                });
+
+                self.texture.update_part(
+                    &Image {
+                        bytes: (tile.y..bottom)
+                            .flat_map(|py| {
+                                let start = (py * self.width + tile.x) as usize;
+                                self.values[start..start + tile.width as usize].iter()
+                            })
+                            .flat_map(|v| coloring.color(*v, self.histogram.as_ref(), pixel_size))
+                            .collect(),
+                        width: tile.width as u16,
+                        height: tile.height as u16,
+                    },
+                    tile.x as i32,
+                    tile.y as i32,
+                    tile.width as i32,
+                    tile.height as i32,
+                );
+            });
+        }
+
+        // The histogram follows the tiles, with a last equalization once they stop arriving
+        if coloring.mode == Mode::Histogram {
+            self.stale |= arrived;
+            if self.stale && (!arrived || get_time() - self.equalized >= EQUALIZE_INTERVAL) {
+                self.recolor(coloring);
+            }
+        }
+    }
+
+    // Color the whole texture again without recomputing it
+    pub fn recolor(&mut self, coloring: &Coloring) {
+        if self.width * self.height == 0 {
+            return;
+        }
+        if coloring.mode == Mode::Histogram {
+            self.histogram = Some(Histogram::new(self.values.iter()));
+            self.stale = false;
+            self.equalized = get_time();
+        }
+        let histogram = self.histogram.as_ref();
+        let pixel_size = self.pixel_size();
+
+        self.texture.update(&Image {
+            bytes: self
+                .values
+                .par_iter()
+                .flat_map_iter(|v| coloring.color(*v, histogram, pixel_size))
+                .collect(),
+            width: self.width as u16,
+            height: self.height as u16,
+        });
+    }
+}
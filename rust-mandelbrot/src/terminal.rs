@@ -0,0 +1,154 @@
+use std::io::{stdout, BufWriter, Write};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{read, Event, KeyCode, KeyModifiers},
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+    QueueableCommand,
+};
+use num_complex::Complex;
+
+use crate::coloring::Coloring;
+use crate::fractal::auto_iterations;
+use crate::headless::{coloring, parse_options, render_image, sampler, viewport, Options};
+use crate::viewport::Viewport;
+use crate::{MOVE_SCALE, ZOOM_FACTOR};
+
+// Upper half block, colored by the top pixel of the cell with the bottom one behind it
+const HALF_BLOCK: char = '\u{2580}';
+
+// Cells of the image, two pixels high each, and the status line below them
+fn draw(viewport: &Viewport, rgb: &[u8], status: &str) -> crossterm::Result<()> {
+    let mut out = BufWriter::new(stdout().lock());
+    let color = |x: u32, y: u32| {
+        let i = ((y * viewport.width + x) * 3) as usize;
+        Color::Rgb {
+            r: rgb[i],
+            g: rgb[i + 1],
+            b: rgb[i + 2],
+        }
+    };
+
+    for row in 0..viewport.height / 2 {
+        out.queue(MoveTo(0, row as u16))?;
+        for x in 0..viewport.width {
+            out.queue(SetForegroundColor(color(x, row * 2)))?
+                .queue(SetBackgroundColor(color(x, row * 2 + 1)))?
+                .queue(Print(HALF_BLOCK))?;
+        }
+    }
+
+    out.queue(ResetColor)?
+        .queue(MoveTo(0, (viewport.height / 2) as u16))?
+        .queue(Clear(ClearType::CurrentLine))?
+        .queue(Print(status))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn run(options: &mut Options, coloring: &Coloring, viewport: &mut Viewport) -> Result<(), String> {
+    let mut redraw = true;
+
+    loop {
+        if redraw {
+            // The last line holds the status
+            let (columns, lines) = size().map_err(|err| err.to_string())?;
+            viewport.resize(columns as u32, lines.saturating_sub(1) as u32 * 2);
+
+            if viewport.width * viewport.height > 0 {
+                let iterations = options
+                    .iterations
+                    .unwrap_or_else(|| auto_iterations(viewport.scale));
+                let rgb = render_image(
+                    viewport,
+                    &sampler(options, viewport, options.julia)?,
+                    coloring,
+                    options.antialiasing,
+                );
+                let (re, im) = viewport.center.to_decimal(viewport.pixel_size());
+                let status = format!(
+                    "{} + {}i, scale = {:e}, iterations = {} ({})",
+                    re,
+                    im,
+                    viewport.scale,
+                    iterations,
+                    if options.iterations.is_some() {
+                        "manual"
+                    } else {
+                        "auto"
+                    }
+                );
+                draw(viewport, &rgb, &status).map_err(|err| err.to_string())?;
+            }
+        }
+
+        let step = (viewport.width.min(viewport.height) as f64 / MOVE_SCALE).round()
+            * viewport.pixel_size();
+        let (x, y) = (viewport.width as f64 / 2., viewport.height as f64 / 2.);
+        let iterations = options
+            .iterations
+            .unwrap_or_else(|| auto_iterations(viewport.scale));
+
+        redraw = match read().map_err(|err| err.to_string())? {
+            Event::Key(key) => {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Left => viewport.translate(Complex::new(-step, 0.)),
+                    KeyCode::Right => viewport.translate(Complex::new(step, 0.)),
+                    KeyCode::Up => viewport.translate(Complex::new(0., -step)),
+                    KeyCode::Down => viewport.translate(Complex::new(0., step)),
+                    KeyCode::Char('+') | KeyCode::Char('=') => {
+                        viewport.zoom_at(x, y, 1. / ZOOM_FACTOR)
+                    }
+                    KeyCode::Char('-') => viewport.zoom_at(x, y, ZOOM_FACTOR),
+                    KeyCode::PageUp => options.iterations = Some(iterations.saturating_mul(2)),
+                    KeyCode::PageDown => options.iterations = Some((iterations / 2).max(1)),
+                    KeyCode::Home => options.iterations = None,
+                    // Nothing to render again
+                    _ => {
+                        redraw = false;
+                        continue;
+                    }
+                }
+                true
+            }
+            Event::Resize(..) => true,
+            _ => false,
+        };
+    }
+}
+
+// Explore in the terminal with 24-bit colors, for sessions without a graphical display
+pub fn explore(args: &[String]) -> Result<(), String> {
+    let mut options = parse_options(args)?;
+    let coloring = coloring(&options)?;
+    let mut viewport = viewport(&options, 0, 0);
+
+    let setup = || -> crossterm::Result<()> {
+        enable_raw_mode()?;
+        stdout().queue(EnterAlternateScreen)?.queue(Hide)?.flush()?;
+        Ok(())
+    };
+    setup().map_err(|err| err.to_string())?;
+
+    let result = run(&mut options, &coloring, &mut viewport);
+
+    // The terminal is given back even when rendering failed
+    let restore = || -> crossterm::Result<()> {
+        stdout()
+            .queue(ResetColor)?
+            .queue(Show)?
+            .queue(LeaveAlternateScreen)?
+            .flush()?;
+        disable_raw_mode()
+    };
+    restore().map_err(|err| err.to_string())?;
+    result
+}
@@ -0,0 +1,110 @@
+use num_complex::Complex;
+
+use crate::deep::{precision, BigComplex, MAX_SCALE, MIN_SCALE};
+
+// Spans that can be rendered, a zero scale has no pixel size and a negative one flips the image
+pub fn check_scale(scale: f64) -> Result<f64, String> {
+    if scale.is_finite() && scale > 0. {
+        Ok(scale)
+    } else {
+        Err(format!("Invalid scale {}", scale))
+    }
+}
+
+// Mapping between the pixels of a pane and the complex plane,
+// scale is the span of the shortest side of the pane
+#[derive(Clone, PartialEq)]
+pub struct Viewport {
+    pub center: BigComplex,
+    pub scale: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new(center: Complex<f64>, scale: f64) -> Self {
+        Self {
+            center: BigComplex::new(center.re, center.im),
+            scale,
+            width: 0,
+            height: 0,
+        }
+    }
+
+    pub fn pixel_size(&self) -> f64 {
+        self.scale / self.width.min(self.height).max(1) as f64
+    }
+
+    pub fn precision(&self) -> usize {
+        precision(self.pixel_size())
+    }
+
+    // Offset from the center of the point under the pixel (x, y)
+    pub fn delta(&self, x: f64, y: f64) -> Complex<f64> {
+        Complex::new(
+            (x - self.width as f64 / 2.) * self.pixel_size(),
+            (y - self.height as f64 / 2.) * self.pixel_size(),
+        )
+    }
+
+    pub fn point(&self, x: f64, y: f64) -> BigComplex {
+        self.center.translate(self.delta(x, y), self.precision())
+    }
+
+    pub fn point_f64(&self, x: f64, y: f64) -> Complex<f64> {
+        self.center.to_f64() + self.delta(x, y)
+    }
+
+    // Pixel at an offset from the center, the inverse of delta
+    pub fn delta_pixel(&self, delta: Complex<f64>) -> (f64, f64) {
+        (
+            delta.re / self.pixel_size() + self.width as f64 / 2.,
+            delta.im / self.pixel_size() + self.height as f64 / 2.,
+        )
+    }
+
+    // Pixel position of a point, the inverse of point_f64
+    pub fn pixel(&self, point: Complex<f64>) -> (f64, f64) {
+        self.delta_pixel(point - self.center.to_f64())
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        (0. ..self.width as f64).contains(&x) && (0. ..self.height as f64).contains(&y)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    // Move the view by a distance in the complex plane
+    pub fn translate(&mut self, delta: Complex<f64>) {
+        self.center = self.center.translate(delta, self.precision());
+    }
+
+    // Move the view so that the content follows a mouse drag of (dx, dy) pixels
+    pub fn drag(&mut self, dx: f64, dy: f64) {
+        self.translate(Complex::new(-dx, -dy) * self.pixel_size());
+    }
+
+    // Zoom by factor while keeping the point under the pixel (x, y) in place
+    pub fn zoom_at(&mut self, x: f64, y: f64, factor: f64) {
+        let factor = factor.clamp(MIN_SCALE / self.scale, MAX_SCALE / self.scale);
+        let delta = self.delta(x, y) * (1. - factor);
+        self.scale *= factor;
+        self.translate(delta);
+    }
+
+    // Fit the view on the pixel rectangle between two corners
+    pub fn zoom_to_rect(&mut self, from: (f64, f64), to: (f64, f64)) {
+        let (width, height) = ((to.0 - from.0).abs(), (to.1 - from.1).abs());
+        if width < 1. || height < 1. {
+            return;
+        }
+
+        let center = self.delta((from.0 + to.0) / 2., (from.1 + to.1) / 2.);
+        let factor = (width / self.width as f64).max(height / self.height as f64);
+        self.translate(center);
+        self.scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+    }
+}